@@ -1,5 +1,5 @@
 // Run as
-//  RUST_MIN_STACK=16777216 cargo run --release --example run
+//  cargo run --release --example run
 
 // const EVERY: usize = 2;
 const EVERY: usize = 3;
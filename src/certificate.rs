@@ -0,0 +1,401 @@
+// Optimality certificates for heap outcomes.
+//
+// Take a sequence of `Insert` and `DeleteMin` operations.  The items left in the heap at the end
+// are a maximum weight basis of the heap matroid: after the first `j` inserts, the heap can hold
+// at most `c_j` items, and a set of items can survive iff no prefix holds more than its capacity.
+//
+// The dual of the linear program is a threshold on item weights, that only goes down along the
+// sequence, and only at prefixes that the kept items fill to capacity ('tight' prefixes).  Kept
+// items have to be at least their threshold, dropped items at most their threshold.  Any dropped
+// item above its threshold is 'light': swapping it in improves the solution.
+//
+// Checking all of that takes a linear number of comparisons, just like in
+// `stackexchange-answer.markdown`.
+//
+// Sequences of `Insert` and `DeleteMax` work the same, with the order of the items reversed:
+// thresholds only go up, kept items have to be at most their threshold, and dropped items at
+// least their threshold.
+
+use std::cmp::Reverse;
+use std::fmt::Debug;
+
+use crate::schubert::{
+    deletes_max, linear_loop, reverse_ops, tag_ops, to_wrapped_ops, MixedDeletes, Operation,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Certificate<T> {
+    /// Tight prefixes, given as the number of inserts they cover.  Strictly increasing, and the
+    /// last one covers all inserts.  Together they split the inserts into segments.
+    pub boundaries: Vec<usize>,
+    /// One threshold per segment.  `None` means there's no threshold at all, ie no item is too
+    /// light to be kept.
+    pub thresholds: Vec<Option<T>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    /// `kept` doesn't have one entry per insert.
+    WrongLength,
+    /// The first `prefix` inserts have more kept items than the heap can hold.
+    Infeasible { prefix: usize },
+    /// The kept items are feasible, but there are too few of them.
+    NotABasis,
+    /// The kept items are optimal, but the certificate doesn't prove it.
+    BadCertificate,
+    /// The kept items are not optimal.  Contains the indices of the inserts of light items.
+    Light(Vec<usize>),
+    /// `ops` mixes `DeleteMin` and `DeleteMax`, so there's no heap matroid to speak of.
+    MixedDeletes,
+}
+
+impl From<MixedDeletes> for Rejection {
+    fn from(MixedDeletes: MixedDeletes) -> Self {
+        Self::MixedDeletes
+    }
+}
+
+/// Borrows the items, and reverses their order, so that a max-deleting sequence becomes a
+/// min-deleting one.
+fn reversed<T>(ops: &[Operation<T>]) -> Vec<Operation<Reverse<&T>>> {
+    reverse_ops(ops.iter().map(Operation::as_ref).collect())
+}
+
+/// How many items the heap can hold after each prefix of inserts.
+///
+/// `capacities[j]` is for the first `j + 1` inserts.  Doesn't compare any items.
+#[must_use]
+pub fn capacities<T>(ops: &[Operation<T>]) -> Vec<usize> {
+    to_wrapped_ops(ops.iter().map(Operation::as_ref).collect())
+        .into_iter()
+        .scan(0, |size, op| {
+            *size += usize::from(!op.has_delete);
+            Some(*size)
+        })
+        .collect()
+}
+
+fn inserted<T>(ops: &[Operation<T>]) -> Vec<&T> {
+    ops.iter()
+        .filter_map(|op| match op {
+            Operation::Insert(x) => Some(x),
+            Operation::DeleteMin | Operation::DeleteMax => None,
+        })
+        .collect()
+}
+
+/// Checks that `kept` is a basis, and returns the tight prefixes.  Doesn't compare any items.
+fn tight_prefixes<T>(ops: &[Operation<T>], kept: &[bool]) -> Result<Vec<usize>, Rejection> {
+    let capacities = capacities(ops);
+    if capacities.len() != kept.len() {
+        return Err(Rejection::WrongLength);
+    }
+    let mut tight = vec![];
+    let mut count = 0;
+    for (prefix, (&capacity, &k)) in (1..).zip(capacities.iter().zip(kept)) {
+        count += usize::from(k);
+        if count > capacity {
+            return Err(Rejection::Infeasible { prefix });
+        }
+        if count == capacity {
+            tight.push(prefix);
+        }
+    }
+    if count < capacities.last().copied().unwrap_or(0) {
+        return Err(Rejection::NotABasis);
+    }
+    Ok(tight)
+}
+
+/// Builds the certificate for `kept`, with a linear number of comparisons.
+///
+/// The threshold of each segment is the smallest kept item up to the end of that segment: those
+/// are exactly the kept items we could swap out for a dropped item from that segment.
+///
+/// Only proves optimality, if `kept` is optimal.  Otherwise, [`verify`] will find light items.
+pub fn certify<T: Ord + Clone>(
+    ops: &[Operation<T>],
+    kept: &[bool],
+) -> Result<Certificate<T>, Rejection> {
+    if deletes_max(ops)? {
+        let Certificate {
+            boundaries,
+            thresholds,
+        } = certify_min(&reversed(ops), kept)?;
+        return Ok(Certificate {
+            boundaries,
+            thresholds: thresholds
+                .into_iter()
+                .map(|t| t.map(|Reverse(x)| x.clone()))
+                .collect(),
+        });
+    }
+    certify_min(ops, kept)
+}
+
+fn certify_min<T: Ord + Clone>(
+    ops: &[Operation<T>],
+    kept: &[bool],
+) -> Result<Certificate<T>, Rejection> {
+    let boundaries = tight_prefixes(ops, kept)?;
+    let items = inserted(ops);
+    let mut thresholds = vec![];
+    let mut running_min: Option<&T> = None;
+    let mut start = 0;
+    for &end in &boundaries {
+        for (item, _) in items[start..end]
+            .iter()
+            .zip(&kept[start..end])
+            .filter(|(_, &k)| k)
+        {
+            running_min = Some(running_min.map_or(*item, |m| m.min(*item)));
+        }
+        thresholds.push(running_min.cloned());
+        start = end;
+    }
+    Ok(Certificate {
+        boundaries,
+        thresholds,
+    })
+}
+
+/// Runs [`linear_loop`], and also returns which inserts survive, together with a certificate
+/// that proves it.
+///
+/// `kept[i]` says whether the item of the `i`th insert survives.
+///
+/// # Panics
+///
+/// Panics if `ops` mixes `DeleteMin` and `DeleteMax`, just like [`linear_loop`].
+#[must_use]
+pub fn certified_linear_loop<T: Ord + Debug + Clone>(
+    ops: &[Operation<T>],
+) -> (Vec<bool>, Certificate<T>) {
+    // Tag items with their index, so that we can tell equal items apart.
+    let tagged = tag_ops(ops.iter().map(|op| op.as_ref().map(T::clone)).collect());
+    let mut kept = vec![false; inserted(ops).len()];
+    for (_, index) in linear_loop(tagged) {
+        kept[index] = true;
+    }
+    let certificate = certify(ops, &kept).expect("linear_loop always finds a basis");
+    (kept, certificate)
+}
+
+/// Checks that `kept` is an optimal outcome of `ops` with a linear number of comparisons.
+///
+/// `kept[i]` says whether the item of the `i`th insert is kept; all other items are dropped.
+///
+/// If the certificate doesn't check out, we build our own from `kept` to find the light items.
+pub fn verify<T: Ord + Clone>(
+    ops: &[Operation<T>],
+    kept: &[bool],
+    certificate: &Certificate<T>,
+) -> Result<(), Rejection> {
+    if deletes_max(ops)? {
+        let certificate = Certificate {
+            boundaries: certificate.boundaries.clone(),
+            thresholds: certificate
+                .thresholds
+                .iter()
+                .map(|t| t.as_ref().map(Reverse))
+                .collect(),
+        };
+        return verify_min(&reversed(ops), kept, &certificate);
+    }
+    verify_min(ops, kept, certificate)
+}
+
+fn verify_min<T: Ord + Clone>(
+    ops: &[Operation<T>],
+    kept: &[bool],
+    certificate: &Certificate<T>,
+) -> Result<(), Rejection> {
+    let boundaries = tight_prefixes(ops, kept)?;
+    if check(&inserted(ops), kept, &boundaries, certificate) {
+        return Ok(());
+    }
+    let own = certify_min(ops, kept)?;
+    let light = light_items(&inserted(ops), kept, &own);
+    if light.is_empty() {
+        Err(Rejection::BadCertificate)
+    } else {
+        Err(Rejection::Light(light))
+    }
+}
+
+fn check<T: Ord>(
+    items: &[&T],
+    kept: &[bool],
+    tight: &[usize],
+    Certificate {
+        boundaries,
+        thresholds,
+    }: &Certificate<T>,
+) -> bool {
+    // Segments may only end at tight prefixes, and the last one has to cover everything.
+    let mut tight = tight.iter();
+    let well_formed = boundaries.len() == thresholds.len()
+        && boundaries.last().copied() == tight.clone().last().copied()
+        && boundaries
+            .iter()
+            .all(|b| tight.by_ref().find(|&t| t >= b) == Some(b));
+    // Thresholds only ever go down.  `None` is above everything.
+    let monotone = thresholds.windows(2).all(|w| match w {
+        [_, None] => w[0].is_none(),
+        [None, Some(_)] => true,
+        [Some(a), Some(b)] => b <= a,
+        _ => unreachable!(),
+    });
+    well_formed
+        && monotone
+        && segments(boundaries)
+            .zip(thresholds)
+            .all(|((start, end), threshold)| {
+                (start..end).all(|i| match threshold {
+                    None => !kept[i],
+                    Some(t) if kept[i] => t <= items[i],
+                    Some(t) => items[i] <= t,
+                })
+            })
+}
+
+fn light_items<T: Ord>(items: &[&T], kept: &[bool], certificate: &Certificate<T>) -> Vec<usize> {
+    segments(&certificate.boundaries)
+        .zip(&certificate.thresholds)
+        .flat_map(|((start, end), threshold)| {
+            (start..end).filter(move |&i| match threshold {
+                Some(t) => !kept[i] && t < items[i],
+                None => false,
+            })
+        })
+        .collect()
+}
+
+fn segments(boundaries: &[usize]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    boundaries
+        .iter()
+        .scan(0, |start, &end| Some((std::mem::replace(start, end), end)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::{any, prop_assert, prop_assert_eq, proptest, Strategy};
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+
+    fn operations() -> impl Strategy<Value = Vec<Operation<u8>>> {
+        proptest::collection::vec(any::<Option<u8>>(), 0..1_000).prop_map(|ops| {
+            ops.into_iter()
+                .map(|op| op.map_or(Operation::DeleteMin, Operation::Insert))
+                .collect()
+        })
+    }
+
+    /// Runs the operations, but always deletes the maximum instead.
+    /// That keeps a basis, but usually a bad one.
+    fn worst_kept(ops: &[Operation<u8>]) -> Vec<bool> {
+        let mut heap = BinaryHeap::new();
+        let mut index = 0;
+        for op in ops {
+            match op {
+                Operation::Insert(x) => {
+                    heap.push((*x, Reverse(index)));
+                    index += 1;
+                }
+                Operation::DeleteMin | Operation::DeleteMax => {
+                    heap.pop();
+                }
+            }
+        }
+        let mut kept = vec![false; index];
+        for (_, Reverse(i)) in heap {
+            kept[i] = true;
+        }
+        kept
+    }
+
+    #[test]
+    fn light_item() {
+        let ops = [
+            Operation::Insert(5),
+            Operation::Insert(1),
+            Operation::DeleteMin,
+            Operation::Insert(3),
+        ];
+        let (kept, certificate) = certified_linear_loop(&ops);
+        assert_eq!(kept, vec![true, false, true]);
+        assert_eq!(verify(&ops, &kept, &certificate), Ok(()));
+        assert_eq!(
+            verify(&ops, &[false, true, true], &certificate),
+            Err(Rejection::Light(vec![0]))
+        );
+        assert_eq!(
+            verify(&ops, &[true, true, true], &certificate),
+            Err(Rejection::Infeasible { prefix: 2 })
+        );
+        assert_eq!(
+            verify(&ops, &[true, false, false], &certificate),
+            Err(Rejection::NotABasis)
+        );
+    }
+
+    #[test]
+    fn mixed_deletes() {
+        let ops = [
+            Operation::Insert(5),
+            Operation::Insert(1),
+            Operation::DeleteMin,
+            Operation::DeleteMax,
+        ];
+        let certificate = Certificate {
+            boundaries: vec![],
+            thresholds: vec![],
+        };
+        assert_eq!(certify(&ops, &[false, false]), Err(Rejection::MixedDeletes));
+        assert_eq!(
+            verify(&ops, &[false, false], &certificate),
+            Err(Rejection::MixedDeletes)
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn linear_loop_is_certified(ops in operations()) {
+            let (kept, certificate) = certified_linear_loop(&ops);
+            prop_assert_eq!(verify(&ops, &kept, &certificate), Ok(()));
+        }
+
+        #[test]
+        fn max_deleting_is_certified(min_ops in operations()) {
+            let ops: Vec<_> = min_ops.iter().copied().map(Operation::flip).collect();
+            let (kept, certificate) = certified_linear_loop(&ops);
+            prop_assert_eq!(verify(&ops, &kept, &certificate), Ok(()));
+            // Deleting minima instead keeps a basis, but usually a bad one.
+            let (worst, _) = certified_linear_loop(&min_ops);
+            match verify(&ops, &worst, &certificate) {
+                Ok(()) | Err(Rejection::BadCertificate) => {}
+                Err(Rejection::Light(light)) => prop_assert!(light.iter().all(|&i| !worst[i])),
+                Err(rejection) => prop_assert!(false, "{rejection:?}"),
+            }
+        }
+
+        #[test]
+        fn finds_light_items(ops in operations()) {
+            let (best, certificate) = certified_linear_loop(&ops);
+            let worst = worst_kept(&ops);
+            let items = inserted(&ops);
+            let weight = |kept: &[bool]| -> usize {
+                items.iter().zip(kept).filter(|(_, &k)| k).map(|(&&x, _)| usize::from(x)).sum()
+            };
+            match verify(&ops, &worst, &certificate) {
+                Ok(()) | Err(Rejection::BadCertificate) => prop_assert_eq!(weight(&worst), weight(&best)),
+                Err(Rejection::Light(light)) => {
+                    prop_assert!(weight(&worst) < weight(&best));
+                    prop_assert!(light.iter().all(|&i| !worst[i]));
+                }
+                Err(rejection) => prop_assert!(false, "{rejection:?}"),
+            }
+        }
+    }
+}
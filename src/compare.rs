@@ -0,0 +1,321 @@
+// Soft heaps with a custom order.
+//
+// `SoftHeap<T>` is a min-heap on `T: Ord`.  `SoftHeapBy` takes a comparator instead: anything
+// that implements `Compare`, including plain closures.  So we can get a max-heap, order by a
+// field, or order floats by `total_cmp`, without writing newtype wrappers like `Reverse`.
+//
+// Comparators have to be stateless, ie zero-sized: every item carries its own copy, see
+// `Compared`.
+
+use std::cmp::Ordering;
+use std::mem;
+
+use crate::merge_strategy::{MergeStrategy, PassH};
+use crate::pairing::SoftHeap;
+
+/// Decides the order of a [`SoftHeapBy`].  Smaller items come out first.
+///
+/// [`SoftHeapBy`] only takes stateless comparators: zero-sized types like [`Natural`], or
+/// closures that don't capture anything.
+pub trait Compare<T: ?Sized> {
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+}
+
+impl<T: ?Sized, F: Fn(&T, &T) -> Ordering> Compare<T> for F {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
+
+/// The order from `Ord`, for a min-heap.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Natural;
+
+impl<T: Ord + ?Sized> Compare<T> for Natural {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+/// The reverse of the order from `Ord`, for a max-heap.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Reversed;
+
+impl<T: Ord + ?Sized> Compare<T> for Reversed {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        b.cmp(a)
+    }
+}
+
+/// Floats ordered by `total_cmp`.  `NaN`s come last, and `-0.0` before `0.0`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TotalOrder;
+
+impl Compare<f64> for TotalOrder {
+    fn compare(&self, a: &f64, b: &f64) -> Ordering {
+        a.total_cmp(b)
+    }
+}
+
+impl Compare<f32> for TotalOrder {
+    fn compare(&self, a: &f32, b: &f32) -> Ordering {
+        a.total_cmp(b)
+    }
+}
+
+/// Orders by the key that `F` extracts, eg a field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ByKey<F>(pub F);
+
+impl<T: ?Sized, K: Ord, F: Fn(&T) -> K> Compare<T> for ByKey<F> {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self.0(a).cmp(&self.0(b))
+    }
+}
+
+/// An item together with the comparator that orders it.
+///
+/// Every item carries its own copy of the comparator, so that the soft heap can compare items
+/// with plain `Ord`.  That's why [`SoftHeapBy`] only takes zero-sized comparators: the copies
+/// cost nothing, and all comparators of the same type agree on the order.
+#[derive(Debug, Clone, Copy)]
+pub struct Compared<T, C> {
+    pub item: T,
+    compare: C,
+}
+
+impl<T, C: Compare<T>> Ord for Compared<T, C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.compare.compare(&self.item, &other.item)
+    }
+}
+impl<T, C: Compare<T>> PartialOrd for Compared<T, C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<T, C: Compare<T>> PartialEq for Compared<T, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl<T, C: Compare<T>> Eq for Compared<T, C> {}
+
+/// A soft heap ordered by the comparator `C`.
+///
+/// Works like the mutable interface of [`SoftHeap`]: corrupted items get reported along the way.
+///
+/// `C` has to be zero-sized, see [`Compared`].  Pushing with a comparator that has state, like a
+/// closure that captures something, fails to compile.
+#[derive(Debug, Clone)]
+pub struct SoftHeapBy<T, C, M = PassH> {
+    pub heap: SoftHeap<Compared<T, C>, M>,
+    pub compare: C,
+}
+
+/// A min-heap, like [`SoftHeap`] itself.
+pub type MinSoftHeap<T, M = PassH> = SoftHeapBy<T, Natural, M>;
+
+/// A max-heap, without wrapping the items in `Reverse`.
+pub type MaxSoftHeap<T, M = PassH> = SoftHeapBy<T, Reversed, M>;
+
+impl<T, C> SoftHeapBy<T, C> {
+    /// See [`SoftHeap::new`].
+    pub fn new(corrupt_every_n: usize, compare: C) -> Self {
+        Self {
+            heap: SoftHeap::new(corrupt_every_n),
+            compare,
+        }
+    }
+
+    /// See [`SoftHeap::with_epsilon`].
+    pub fn with_epsilon(epsilon: f64, compare: C) -> Self {
+        Self {
+            heap: SoftHeap::with_epsilon(epsilon),
+            compare,
+        }
+    }
+}
+
+// So that `MinSoftHeap` and `MaxSoftHeap` don't need their comparator spelled out.
+impl<T, C: Default> SoftHeapBy<T, C> {
+    /// Like [`SoftHeapBy::new`], with the default comparator.
+    #[must_use]
+    pub fn new_default(corrupt_every_n: usize) -> Self {
+        Self::new(corrupt_every_n, C::default())
+    }
+
+    /// Like [`SoftHeapBy::with_epsilon`], with the default comparator.
+    #[must_use]
+    pub fn with_epsilon_default(epsilon: f64) -> Self {
+        Self::with_epsilon(epsilon, C::default())
+    }
+}
+
+impl<T, C, M: MergeStrategy> SoftHeapBy<T, C, M> {
+    /// See [`SoftHeap::with_strategy`].
+    pub fn with_strategy(corrupt_every_n: usize, strategy: M, compare: C) -> Self {
+        Self {
+            heap: SoftHeap::with_strategy(corrupt_every_n, strategy),
+            compare,
+        }
+    }
+
+    pub const fn epsilon(&self) -> f64 {
+        self.heap.epsilon()
+    }
+    pub fn count_corrupted(&self) -> usize {
+        self.heap.count_corrupted()
+    }
+    pub fn count_uncorrupted(&self) -> usize {
+        self.heap.count_uncorrupted()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// The root item, ie the smallest apparent key according to `C`.
+    pub fn peek(&self) -> Option<&T> {
+        self.heap.peek().map(|compared| &compared.item)
+    }
+
+    /// All items still in the heap, corrupted or not, in no particular order.
+    pub fn into_all_items(self) -> Vec<T> {
+        unwrap_all(self.heap.into_all_items())
+    }
+}
+
+impl<T, C: Compare<T> + Clone, M: MergeStrategy> SoftHeapBy<T, C, M> {
+    pub fn push(&mut self, item: T) {
+        const {
+            assert!(
+                mem::size_of::<C>() == 0,
+                "SoftHeapBy only takes stateless comparators"
+            );
+        }
+        self.heap.push(Compared {
+            item,
+            compare: self.compare.clone(),
+        });
+    }
+
+    /// See [`SoftHeap::pop`].
+    pub fn pop(&mut self) -> (Option<T>, Vec<T>) {
+        let (item, corrupted) = self.heap.pop();
+        (item.map(|compared| compared.item), unwrap_all(corrupted))
+    }
+
+    /// See [`SoftHeap::heavy_pop`].
+    pub fn heavy_pop(&mut self) -> (Option<T>, Vec<T>) {
+        let (item, corrupted) = self.heap.heavy_pop();
+        (item.map(|compared| compared.item), unwrap_all(corrupted))
+    }
+
+    /// Moves all items of `other` into `self`, leaving `other` empty.
+    ///
+    /// Comparators are stateless, so both heaps use the same order.
+    pub fn append(&mut self, other: &mut Self) {
+        self.heap.append(&mut other.heap);
+    }
+}
+
+impl<T, C: Compare<T> + Clone, M: MergeStrategy> Extend<T> for SoftHeapBy<T, C, M> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+impl<T, C, M> From<SoftHeapBy<T, C, M>> for Vec<T> {
+    fn from(heap: SoftHeapBy<T, C, M>) -> Self {
+        unwrap_all(Vec::from(heap.heap))
+    }
+}
+
+fn unwrap_all<T, C>(items: Vec<Compared<T, C>>) -> Vec<T> {
+    items.into_iter().map(|compared| compared.item).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::{prop_assert_eq, proptest};
+
+    /// Pops everything, in the order the heap gives it out.  Corrupted items come out whenever
+    /// they get reported.
+    fn drain<T, C: Compare<T> + Clone>(mut heap: SoftHeapBy<T, C>) -> Vec<T> {
+        let mut items = vec![];
+        while !heap.is_empty() {
+            let (item, corrupted) = heap.pop();
+            items.extend(corrupted);
+            items.extend(item);
+        }
+        items
+    }
+
+    proptest! {
+        #[test]
+        fn max_heap_sorts_descending(mut items: Vec<i32>) {
+            // Without corruption, that's heap sort.
+            let mut heap = MaxSoftHeap::new_default(usize::MAX);
+            heap.extend(items.iter().copied());
+            items.sort_unstable_by(|a, b| b.cmp(a));
+            prop_assert_eq!(drain(heap), items);
+        }
+
+        #[test]
+        fn min_heap_keeps_everything(mut items: Vec<i32>) {
+            let mut heap = MinSoftHeap::with_epsilon_default(0.25);
+            heap.extend(items.iter().copied());
+            let mut drained = drain(heap);
+            drained.sort_unstable();
+            items.sort_unstable();
+            prop_assert_eq!(drained, items);
+        }
+    }
+
+    #[test]
+    fn floats_by_total_order() {
+        let mut heap = SoftHeapBy::new(usize::MAX, TotalOrder);
+        heap.extend([1.5, f64::NAN, -0.0, 0.0, f64::NEG_INFINITY]);
+        let drained = drain(heap);
+        assert_eq!(drained[0].total_cmp(&f64::NEG_INFINITY), Ordering::Equal);
+        assert!(drained[1].is_sign_negative() && drained[2].is_sign_positive());
+        assert!(drained[4].is_nan());
+    }
+
+    struct Job {
+        name: &'static str,
+        deadline: u32,
+    }
+
+    #[test]
+    fn by_field_and_by_closure() {
+        let jobs = [("b", 3), ("a", 7), ("c", 1)].map(|(name, deadline)| Job { name, deadline });
+        let mut heap = SoftHeapBy::new(usize::MAX, ByKey(|job: &Job| job.deadline));
+        heap.extend(jobs);
+        let names: Vec<_> = drain(heap).into_iter().map(|job| job.name).collect();
+        assert_eq!(names, ["c", "b", "a"]);
+
+        let mut heap = SoftHeapBy::new(usize::MAX, |a: &&str, b: &&str| a.len().cmp(&b.len()));
+        heap.extend(["ccc", "a", "bb"]);
+        assert_eq!(drain(heap), ["a", "bb", "ccc"]);
+    }
+
+    #[test]
+    fn copies_are_free() {
+        let by_len = |a: &&str, b: &&str| a.len().cmp(&b.len());
+        assert_eq!(
+            mem::size_of::<Compared<u64, Reversed>>(),
+            mem::size_of::<u64>()
+        );
+        assert_eq!(
+            mem::size_of_val(&Compared {
+                item: "a",
+                compare: by_len
+            }),
+            mem::size_of::<&str>()
+        );
+    }
+}
@@ -0,0 +1,117 @@
+// Key-value entries for soft heaps.
+//
+// `SoftHeap<T>` compares whole items.  Wrap items in `Entry` to order them by a key alone, and
+// carry along a payload that doesn't need to be `Ord`: a task ID, or a mutable handle back into
+// the caller's arrays.
+
+use std::cmp::Ordering;
+
+use crate::merge_strategy::PassH;
+use crate::pairing::SoftHeap;
+
+/// A `key` with a `value` attached.  Ordered by `key` alone.
+///
+/// Equality also only looks at the key, so two entries with equal keys are equal, whatever their
+/// values.
+#[derive(Debug, Clone, Copy)]
+pub struct Entry<K, V> {
+    pub key: K,
+    pub value: V,
+}
+
+impl<K, V> Entry<K, V> {
+    pub const fn new(key: K, value: V) -> Self {
+        Self { key, value }
+    }
+
+    pub fn into_pair(self) -> (K, V) {
+        (self.key, self.value)
+    }
+}
+
+impl<K, V> From<(K, V)> for Entry<K, V> {
+    fn from((key, value): (K, V)) -> Self {
+        Self { key, value }
+    }
+}
+
+impl<K: Ord, V> Ord for Entry<K, V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+impl<K: Ord, V> PartialOrd for Entry<K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<K: Ord, V> PartialEq for Entry<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl<K: Ord, V> Eq for Entry<K, V> {}
+
+/// A soft heap ordered by `K`, that carries a `V` with every key.
+pub type KeyValueHeap<K, V, M = PassH> = SoftHeap<Entry<K, V>, M>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::{prop_assert, prop_assert_eq, proptest};
+
+    /// Not `Ord`, not even `PartialEq`.
+    #[derive(Debug)]
+    struct Task {
+        id: usize,
+        weight: f64,
+    }
+
+    proptest! {
+        #[test]
+        fn orders_by_key_only(keys: Vec<u8>) {
+            let mut heap: KeyValueHeap<u8, Task> = SoftHeap::with_epsilon(0.25);
+            for (id, &key) in keys.iter().enumerate() {
+                heap.push(Entry::new(key, Task { id, weight: f64::from(key) / 2.0 }));
+            }
+
+            let mut reported = vec![];
+            loop {
+                let (item, witnessed) = heap.pop();
+                reported.extend(witnessed);
+                if item.is_none() && heap.is_empty() {
+                    break;
+                }
+                reported.extend(item);
+            }
+
+            // Every payload comes back exactly once, still attached to its key.
+            let mut ids: Vec<usize> = reported.iter().map(|entry| entry.value.id).collect();
+            ids.sort_unstable();
+            prop_assert_eq!(ids, (0..keys.len()).collect::<Vec<_>>());
+            for Entry { key, value } in reported {
+                prop_assert_eq!(keys[value.id], key);
+                prop_assert!((value.weight - f64::from(key) / 2.0).abs() < f64::EPSILON);
+            }
+        }
+    }
+
+    #[test]
+    fn mutable_handles() {
+        // Like `schubert::approximate_heap_oracle`: pop mutable handles, and write through them.
+        let keys = [5, 3, 8, 1];
+        let mut marks = [false; 4];
+        let mut heap: KeyValueHeap<i32, &mut bool> = SoftHeap::new(100);
+        for (&key, mark) in keys.iter().zip(&mut marks) {
+            heap.push((key, mark).into());
+        }
+        let (Some(Entry { key, value }), witnessed) = heap.pop() else {
+            panic!("heap is not empty");
+        };
+        assert!(witnessed.is_empty());
+        assert_eq!(key, 1);
+        *value = true;
+        drop(heap);
+        assert_eq!(marks, [false, false, false, true]);
+    }
+}
@@ -1,9 +1,17 @@
 #![warn(clippy::pedantic)]
 #![allow(clippy::missing_errors_doc)]
 #![allow(clippy::missing_panics_doc)]
-#![feature(iter_intersperse)]
 
+pub mod certificate;
+pub mod compare;
+pub mod entry;
+pub mod matroid;
+pub mod merge_strategy;
+pub mod mst;
 pub mod pairing;
+pub mod schedule;
 pub mod schubert;
+pub mod select;
 pub mod tools;
+mod tree;
 pub mod witness_set;
@@ -0,0 +1,376 @@
+// Heap matroids, also known as nested or Schubert matroids.
+//
+// See 'Appending: Nerding out' in `stackexchange-answer-softheap.markdown`: run a sequence of
+// inserts and deletes, where every delete may remove any element.  All the possible outcomes are
+// the bases of a heap matroid.
+//
+// Equivalently, start from the empty matroid, and add one element at a time, either as a coloop
+// or as a free extension.  That's what `schubert::WrappedOp` encodes with `has_delete`: an insert
+// that's followed by a delete is a free extension, any other insert is a coloop.  We spell it out
+// here as `Extension`.
+//
+// The dual adds the same elements in reverse order, and swaps coloops and free extensions.
+
+use std::fmt::Debug;
+
+use crate::schubert::{linear_loop, to_wrapped_ops, Operation, WrappedOp};
+
+/// How an element extends the matroid of the elements before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Extension {
+    /// The element is in every basis, so it adds one to the rank.
+    Coloop,
+    /// The element is in general position: it can join any independent set that isn't spanning
+    /// yet.  The rank stays the same.
+    Free,
+}
+
+impl Extension {
+    /// The dual kind of extension.
+    #[must_use]
+    pub const fn dual(self) -> Self {
+        match self {
+            Self::Coloop => Self::Free,
+            Self::Free => Self::Coloop,
+        }
+    }
+}
+
+/// A heap matroid over the elements `0..len()`.  Element `i` has weight `items()[i]`.
+///
+/// Sets of elements are slices of indices, in any order.  Duplicates count once.  Every query
+/// takes linear time.
+///
+/// # Panics
+///
+/// Queries panic if a set has an index that's out of range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeapMatroid<T> {
+    items: Vec<T>,
+    extensions: Vec<Extension>,
+    /// Whether we add the elements from last to first.  Taking the dual flips this, so that
+    /// every element keeps its index.
+    reversed: bool,
+}
+
+impl<T> HeapMatroid<T> {
+    /// Adds the `items` one after the other, each with its kind of extension.
+    pub fn new(items: impl IntoIterator<Item = (T, Extension)>) -> Self {
+        let (items, extensions) = items.into_iter().unzip();
+        Self {
+            items,
+            extensions,
+            reversed: false,
+        }
+    }
+
+    /// The outcomes of `ops`, where every delete may remove any element.
+    ///
+    /// It doesn't matter whether the deletes are `DeleteMin` or `DeleteMax`.  Deletes that have
+    /// nothing to delete don't do anything.
+    #[must_use]
+    pub fn from_ops(ops: Vec<Operation<T>>) -> Self {
+        Self::from(to_wrapped_ops(ops))
+    }
+
+    /// The weights of the elements.
+    #[must_use]
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// How element `i` extends the elements added before it.
+    #[must_use]
+    pub fn extension(&self, i: usize) -> Extension {
+        self.extensions[i]
+    }
+
+    /// The size of every basis.
+    #[must_use]
+    pub fn full_rank(&self) -> usize {
+        self.extensions
+            .iter()
+            .filter(|&&extension| extension == Extension::Coloop)
+            .count()
+    }
+
+    /// The dual matroid, over the same elements.  Its bases are the complements of our bases.
+    #[must_use]
+    pub fn dual(self) -> Self {
+        Self {
+            items: self.items,
+            extensions: self.extensions.into_iter().map(Extension::dual).collect(),
+            reversed: !self.reversed,
+        }
+    }
+
+    /// Whether `set` is a subset of some basis.
+    #[must_use]
+    pub fn is_independent(&self, set: &[usize]) -> bool {
+        let mask = self.mask(set);
+        let (mut rank, mut size) = (0, 0);
+        for (i, extension) in self.steps() {
+            if mask[i] {
+                // A free element can only join a set that doesn't span what came before.
+                if extension == Extension::Free && size >= rank {
+                    return false;
+                }
+                size += 1;
+            }
+            if extension == Extension::Coloop {
+                rank += 1;
+            }
+        }
+        true
+    }
+
+    /// The size of the biggest independent subset of `set`.
+    #[must_use]
+    pub fn rank(&self, set: &[usize]) -> usize {
+        let mask = self.mask(set);
+        // Greedy works for any matroid, in any order.  In the order we add the elements, we
+        // only need to check the element that we add.
+        let (mut rank, mut taken) = (0, 0);
+        for (i, extension) in self.steps() {
+            if mask[i] && (extension == Extension::Coloop || taken < rank) {
+                taken += 1;
+            }
+            if extension == Extension::Coloop {
+                rank += 1;
+            }
+        }
+        taken
+    }
+
+    /// Whether `set` is a basis, ie a maximal independent set.
+    #[must_use]
+    pub fn is_basis(&self, set: &[usize]) -> bool {
+        let size = self.mask(set).into_iter().filter(|&in_set| in_set).count();
+        size == self.full_rank() && self.is_independent(set)
+    }
+
+    /// The elements in the order we add them.
+    fn steps(&self) -> impl Iterator<Item = (usize, Extension)> + '_ {
+        let order: Box<dyn Iterator<Item = usize>> = if self.reversed {
+            Box::new((0..self.len()).rev())
+        } else {
+            Box::new(0..self.len())
+        };
+        order.map(|i| (i, self.extensions[i]))
+    }
+
+    fn mask(&self, set: &[usize]) -> Vec<bool> {
+        let mut mask = vec![false; self.len()];
+        for &i in set {
+            mask[i] = true;
+        }
+        mask
+    }
+
+    /// The indices that `mask` marks, in increasing order.
+    fn indices(mask: &[bool]) -> Vec<usize> {
+        (0..mask.len()).filter(|&i| mask[i]).collect()
+    }
+}
+
+impl<T: Ord + Debug> HeapMatroid<T> {
+    /// A basis with the biggest total weight, in increasing order of indices.
+    ///
+    /// That's what's left at the end, when every delete removes the smallest element, ie when we
+    /// run a min-heap.  We run it with [`linear_loop`], so this takes linear time.  Ties go to
+    /// the element with the bigger index.
+    #[must_use]
+    pub fn max_weight_basis(&self) -> Vec<usize> {
+        self.survivors(Operation::DeleteMin)
+    }
+
+    /// A basis with the smallest total weight, in increasing order of indices.
+    ///
+    /// Like [`HeapMatroid::max_weight_basis`], but with a max-heap.  Ties go to the element with
+    /// the smaller index.
+    #[must_use]
+    pub fn min_weight_basis(&self) -> Vec<usize> {
+        self.survivors(Operation::DeleteMax)
+    }
+
+    fn survivors(&self, delete: Operation<(&T, usize)>) -> Vec<usize> {
+        let mut ops = vec![];
+        for (i, extension) in self.steps() {
+            ops.push(Operation::Insert((&self.items[i], i)));
+            if extension == Extension::Free {
+                ops.push(delete);
+            }
+        }
+        let mut mask = vec![false; self.len()];
+        for (_, i) in linear_loop(ops) {
+            mask[i] = true;
+        }
+        Self::indices(&mask)
+    }
+}
+
+impl<T> From<Vec<WrappedOp<T>>> for HeapMatroid<T> {
+    /// An insert with `has_delete` is a free extension, any other insert is a coloop.
+    fn from(ops: Vec<WrappedOp<T>>) -> Self {
+        Self::new(ops.into_iter().map(|WrappedOp { item, has_delete }| {
+            let extension = if has_delete {
+                Extension::Free
+            } else {
+                Extension::Coloop
+            };
+            (item, extension)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::{prop_assert, prop_assert_eq, proptest, Strategy};
+    use std::collections::BTreeSet;
+
+    fn matroids() -> impl Strategy<Value = HeapMatroid<u32>> {
+        let extension = proptest::bool::ANY.prop_map(|free| {
+            if free {
+                Extension::Free
+            } else {
+                Extension::Coloop
+            }
+        });
+        (
+            proptest::collection::vec((0..20_u32, extension), 0..9),
+            proptest::bool::ANY,
+        )
+            .prop_map(|(items, dual)| {
+                let matroid = HeapMatroid::new(items);
+                if dual {
+                    matroid.dual()
+                } else {
+                    matroid
+                }
+            })
+    }
+
+    /// All outcomes of running the matroid's operations, where deletes may remove anything.
+    fn naive_bases<T>(matroid: &HeapMatroid<T>) -> BTreeSet<Vec<usize>> {
+        let mut outcomes = BTreeSet::from([vec![]]);
+        for (i, extension) in matroid.steps() {
+            outcomes = outcomes
+                .into_iter()
+                .flat_map(|mut outcome: Vec<usize>| {
+                    outcome.push(i);
+                    outcome.sort_unstable();
+                    match extension {
+                        Extension::Coloop => vec![outcome],
+                        Extension::Free => (0..outcome.len())
+                            .map(|j| {
+                                let mut outcome = outcome.clone();
+                                outcome.remove(j);
+                                outcome
+                            })
+                            .collect(),
+                    }
+                })
+                .collect();
+        }
+        outcomes
+    }
+
+    fn subsets(n: usize) -> impl Iterator<Item = Vec<usize>> {
+        (0..1_usize << n).map(move |bits| (0..n).filter(|i| bits >> i & 1 == 1).collect())
+    }
+
+    fn weight(matroid: &HeapMatroid<u32>, set: &[usize]) -> u32 {
+        set.iter().map(|&i| matroid.items()[i]).sum()
+    }
+
+    proptest! {
+        #[test]
+        fn queries_match_naive(matroid in matroids()) {
+            let bases = naive_bases(&matroid);
+            for set in subsets(matroid.len()) {
+                let rank = bases
+                    .iter()
+                    .map(|basis| set.iter().filter(|i| basis.contains(i)).count())
+                    .max()
+                    .unwrap();
+                prop_assert_eq!(matroid.rank(&set), rank);
+                prop_assert_eq!(matroid.is_independent(&set), rank == set.len());
+                prop_assert_eq!(matroid.is_basis(&set), bases.contains(&set));
+            }
+        }
+
+        #[test]
+        fn dual_bases_are_complements(matroid in matroids()) {
+            let n = matroid.len();
+            let dual = matroid.clone().dual();
+            let complement = |basis: &Vec<usize>| -> Vec<usize> {
+                (0..n).filter(|i| !basis.contains(i)).collect()
+            };
+            let dual_bases: BTreeSet<_> = naive_bases(&matroid).iter().map(complement).collect();
+            prop_assert_eq!(naive_bases(&dual), dual_bases);
+            prop_assert_eq!(dual.full_rank(), n - matroid.full_rank());
+            prop_assert_eq!(dual.dual(), matroid);
+        }
+
+        #[test]
+        fn weighted_bases_are_optimal(matroid in matroids()) {
+            let bases = naive_bases(&matroid);
+            let weights: Vec<u32> = bases.iter().map(|basis| weight(&matroid, basis)).collect();
+
+            let max = matroid.max_weight_basis();
+            prop_assert!(bases.contains(&max));
+            prop_assert_eq!(Some(weight(&matroid, &max)), weights.iter().copied().max());
+
+            let min = matroid.min_weight_basis();
+            prop_assert!(bases.contains(&min));
+            prop_assert_eq!(Some(weight(&matroid, &min)), weights.iter().copied().min());
+        }
+
+        #[test]
+        fn from_ops_matches_heap_outcomes(ops in proptest::collection::vec(proptest::option::of(0..20_u32), 0..9)) {
+            let ops: Vec<Operation<u32>> = ops
+                .into_iter()
+                .map(|op| op.map_or(Operation::DeleteMin, Operation::Insert))
+                .collect();
+            // Run all the heap operations directly, and let every delete remove anything.
+            let mut outcomes = BTreeSet::from([vec![]]);
+            let mut index = 0;
+            for op in &ops {
+                outcomes = outcomes
+                    .into_iter()
+                    .flat_map(|outcome: Vec<usize>| match op {
+                        Operation::Insert(_) => {
+                            let mut outcome = outcome;
+                            outcome.push(index);
+                            vec![outcome]
+                        }
+                        _ if outcome.is_empty() => vec![outcome],
+                        _ => (0..outcome.len())
+                            .map(|j| {
+                                let mut outcome = outcome.clone();
+                                outcome.remove(j);
+                                outcome
+                            })
+                            .collect(),
+                    })
+                    .collect();
+                if matches!(op, Operation::Insert(_)) {
+                    index += 1;
+                }
+            }
+            let matroid = HeapMatroid::from_ops(ops);
+            prop_assert_eq!(naive_bases(&matroid), outcomes);
+        }
+    }
+}
@@ -0,0 +1,567 @@
+// Strategies for merging the children of a deleted root in a soft pairing heap.
+//
+// That's where all the corruption happens, and there are lots of ways to do it.  These used to
+// live as commented-out alternatives in `pairing.rs`.  Now they all implement `MergeStrategy`,
+// so we can run them against each other in one binary.
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+
+use itertools::{chain, enumerate, Itertools};
+use rand::seq::SliceRandom;
+
+use crate::pairing::{Report, UnboundWitnessed};
+
+pub trait MergeStrategy: Copy + Debug {
+    /// Merges `items` into one heap.  Corrupts some items along the way, but no more than about
+    /// one per `corrupt_every_n` comparisons.
+    ///
+    /// Corruption happens via [`UnboundWitnessed::corrupt`], which calls back into this strategy.
+    fn merge<T: Ord, R: Report<T>>(
+        self,
+        corrupt_every_n: usize,
+        items: Vec<UnboundWitnessed<T, R>>,
+    ) -> Option<UnboundWitnessed<T, R>>;
+}
+
+/// Corrupts `item`, unless it's a lone leaf that has nothing to pool into.
+///
+/// Only for the strategies that can end up corrupting a heap they didn't merge themselves.
+fn corrupt_unless_leaf<T: Ord, R: Report<T>, M: MergeStrategy>(
+    item: UnboundWitnessed<T, R>,
+    corrupt_every_n: usize,
+    strategy: M,
+) -> UnboundWitnessed<T, R> {
+    if item.pairing.children.is_empty() {
+        item
+    } else {
+        item.corrupt(corrupt_every_n, strategy)
+    }
+}
+
+/// Multi-pass merge in chunks of `corrupt_every_n`, corrupting each full chunk.
+///
+/// See 'A Nearly-Tight Analysis of Multipass Pairing Heaps' by Corwin Sinnamon and Robert E.
+/// Tarjan, <https://epubs.siam.org/doi/epdf/10.1137/1.9781611977554.ch23>, for why multi-pass
+/// gives `O(log n)` delete-min.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PassH;
+
+impl MergeStrategy for PassH {
+    fn merge<T: Ord, R: Report<T>>(
+        self,
+        corrupt_every_n: usize,
+        mut items: Vec<UnboundWitnessed<T, R>>,
+    ) -> Option<UnboundWitnessed<T, R>> {
+        let start = (items.len() + 1)
+            .next_multiple_of(corrupt_every_n)
+            .saturating_sub(corrupt_every_n);
+
+        assert_eq!(0, start % corrupt_every_n);
+        assert!(items.len() - start <= corrupt_every_n);
+
+        let last = UnboundWitnessed::merge_many(items.drain(start..));
+        let binding = items.into_iter().chunks(corrupt_every_n);
+        let chunked = binding
+            .into_iter()
+            .filter_map(UnboundWitnessed::merge_many)
+            .map(|x| x.corrupt(corrupt_every_n, self));
+        UnboundWitnessed::merge_many(chain!(chunked, last))
+    }
+}
+
+/// Like [`PassH`], but the corrupted chunks go to the back of a queue, and get merged again.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PassHQueueSimple;
+
+impl MergeStrategy for PassHQueueSimple {
+    fn merge<T: Ord, R: Report<T>>(
+        self,
+        corrupt_every_n: usize,
+        items: Vec<UnboundWitnessed<T, R>>,
+    ) -> Option<UnboundWitnessed<T, R>> {
+        let mut queue = VecDeque::from(items);
+        while queue.len() > corrupt_every_n {
+            let chunk = UnboundWitnessed::merge_many(queue.drain(..corrupt_every_n))
+                .expect("We should have have a non-empty heap after merging a full chunk.");
+            queue.push_back(chunk.corrupt(corrupt_every_n, self));
+        }
+        UnboundWitnessed::merge_many(queue)
+    }
+}
+
+/// Merges by incrementing a binary counter, then corrupts every `corrupt_every_n`th step of
+/// rolling the counter up.
+///
+/// The counter's work is already paid for by the inserts, so it doesn't corrupt anything.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MultiPassBinary;
+
+/// How many roll-up steps [`MultiPassBinary`] and [`MultiPassBinaryImplicit`] get for free.
+const BOUND: usize = 2;
+
+impl MergeStrategy for MultiPassBinary {
+    fn merge<T: Ord, R: Report<T>>(
+        self,
+        corrupt_every_n: usize,
+        items: Vec<UnboundWitnessed<T, R>>,
+    ) -> Option<UnboundWitnessed<T, R>> {
+        let mut digits: Vec<Option<UnboundWitnessed<T, R>>> = vec![];
+        for item in items {
+            let mut carry = item;
+            // Make sure that we always have one trailing zero, ie trailing None,
+            // so that standard carry logic works.
+            if !matches!(digits.last(), Some(None)) {
+                digits.push(None);
+            }
+            for digit in &mut digits {
+                match digit.take() {
+                    None => {
+                        *digit = Some(carry);
+                        break;
+                    }
+                    Some(digit_item) => carry = carry.meld(digit_item),
+                }
+            }
+        }
+        // Roll up our counter.
+        let mut digits = digits.into_iter().flatten();
+        let mut carry = digits.next()?;
+        for (i, digit) in enumerate(digits) {
+            carry = carry.meld(digit);
+            if (i.saturating_sub(BOUND) + 1).is_multiple_of(corrupt_every_n) {
+                carry = carry.corrupt(corrupt_every_n, self);
+            }
+        }
+        Some(carry)
+    }
+}
+
+/// Multi-pass merge in pairs, that corrupts whenever a pass goes from an odd to an even number
+/// of heaps, after the first [`BOUND`] times.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MultiPassBinaryImplicit;
+
+impl MergeStrategy for MultiPassBinaryImplicit {
+    fn merge<T: Ord, R: Report<T>>(
+        self,
+        corrupt_every_n: usize,
+        mut items: Vec<UnboundWitnessed<T, R>>,
+    ) -> Option<UnboundWitnessed<T, R>> {
+        let mut counter: usize = 0;
+        while items.len() > 1 {
+            let len = items.len();
+            items = items
+                .into_iter()
+                .chunks(2)
+                .into_iter()
+                .filter_map(|chunk| chunk.reduce(UnboundWitnessed::meld))
+                .collect();
+            if len % 4 == 3 {
+                counter += 1;
+                if counter > BOUND && (counter - BOUND).is_multiple_of(corrupt_every_n) {
+                    if let Some(last) = items.pop() {
+                        items.push(corrupt_unless_leaf(last, corrupt_every_n, self));
+                    }
+                }
+            }
+        }
+        items.pop()
+    }
+}
+
+/// Multi-pass merge from a queue, corrupting every `corrupt_every_n`th meld.
+///
+/// Works, but corrupts more than [`PassH`] for the same parameter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Evenly;
+
+impl MergeStrategy for Evenly {
+    fn merge<T: Ord, R: Report<T>>(
+        self,
+        corrupt_every_n: usize,
+        items: Vec<UnboundWitnessed<T, R>>,
+    ) -> Option<UnboundWitnessed<T, R>> {
+        let mut queue = VecDeque::from(items);
+        for c in 1_usize.. {
+            let next = match (queue.pop_front(), queue.pop_front()) {
+                (Some(a), Some(b)) => a.meld(b),
+                (a, _) => return a,
+            };
+            queue.push_back(if c.is_multiple_of(corrupt_every_n) {
+                next.corrupt(corrupt_every_n, self)
+            } else {
+                next
+            });
+        }
+        unreachable!()
+    }
+}
+
+/// Classic two-pass pairing, where the second pass corrupts after every chunk of
+/// `corrupt_every_n` pairs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TwoPass;
+
+impl MergeStrategy for TwoPass {
+    fn merge<T: Ord, R: Report<T>>(
+        self,
+        corrupt_every_n: usize,
+        items: Vec<UnboundWitnessed<T, R>>,
+    ) -> Option<UnboundWitnessed<T, R>> {
+        items
+            .into_iter()
+            .chunks(2)
+            .into_iter()
+            .filter_map(|chunk| chunk.reduce(UnboundWitnessed::meld))
+            .chunks(corrupt_every_n)
+            .into_iter()
+            .fold(None, |acc, chunk| {
+                chain!(
+                    acc.map(|acc: UnboundWitnessed<T, R>| acc.corrupt(corrupt_every_n, self)),
+                    chunk
+                )
+                .reduce(UnboundWitnessed::meld)
+            })
+    }
+}
+
+/// Merges chunks of `corrupt_every_n` from the front of a queue, and puts the corrupted result
+/// back at the front.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TwoPassGrouped;
+
+impl MergeStrategy for TwoPassGrouped {
+    fn merge<T: Ord, R: Report<T>>(
+        self,
+        corrupt_every_n: usize,
+        items: Vec<UnboundWitnessed<T, R>>,
+    ) -> Option<UnboundWitnessed<T, R>> {
+        let mut queue = VecDeque::from(items);
+        while queue.len() >= corrupt_every_n {
+            let chunk = UnboundWitnessed::merge_many(queue.drain(..corrupt_every_n))
+                .expect("We should have have a non-empty heap after merging a full chunk.");
+            queue.push_front(chunk.corrupt(corrupt_every_n, self));
+        }
+        UnboundWitnessed::merge_many(queue)
+    }
+}
+
+/// Like [`TwoPassGrouped`], but puts the corrupted result at the back of the queue.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TwoPassGroupedLast;
+
+impl MergeStrategy for TwoPassGroupedLast {
+    fn merge<T: Ord, R: Report<T>>(
+        self,
+        corrupt_every_n: usize,
+        items: Vec<UnboundWitnessed<T, R>>,
+    ) -> Option<UnboundWitnessed<T, R>> {
+        let mut queue = VecDeque::from(items);
+        while queue.len() >= corrupt_every_n {
+            let chunk = UnboundWitnessed::merge_many(queue.drain(..corrupt_every_n))
+                .expect("We should have have a non-empty heap after merging a full chunk.");
+            queue.push_back(chunk.corrupt(corrupt_every_n, self));
+        }
+        UnboundWitnessed::merge_many(queue)
+    }
+}
+
+/// Merges chunks of `corrupt_every_n` for free, then corrupts the merge of every
+/// [`EVERY_SECOND_LAYER`] chunks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MultiGrouped;
+
+/// Chunk size of the second layer of [`MultiGrouped`] and [`MultiGroupedLessGrace`].
+const EVERY_SECOND_LAYER: usize = 4;
+
+impl MergeStrategy for MultiGrouped {
+    fn merge<T: Ord, R: Report<T>>(
+        self,
+        corrupt_every_n: usize,
+        items: Vec<UnboundWitnessed<T, R>>,
+    ) -> Option<UnboundWitnessed<T, R>> {
+        let binding = items.into_iter().chunks(corrupt_every_n);
+        let mut queue: VecDeque<_> = binding
+            .into_iter()
+            .filter_map(UnboundWitnessed::merge_many)
+            .collect();
+        while queue.len() >= EVERY_SECOND_LAYER {
+            let chunk = UnboundWitnessed::merge_many(queue.drain(..EVERY_SECOND_LAYER))
+                .expect("We should have have a non-empty heap after merging a full chunk.");
+            queue.push_back(chunk.corrupt(corrupt_every_n, self));
+        }
+        UnboundWitnessed::merge_many(queue)
+    }
+}
+
+/// Like [`MultiGrouped`], but corrupts each chunk before merging, and puts the result at the
+/// front of the queue.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MultiGroupedLessGrace;
+
+impl MergeStrategy for MultiGroupedLessGrace {
+    fn merge<T: Ord, R: Report<T>>(
+        self,
+        corrupt_every_n: usize,
+        items: Vec<UnboundWitnessed<T, R>>,
+    ) -> Option<UnboundWitnessed<T, R>> {
+        let binding = items.into_iter().chunks(corrupt_every_n);
+        let mut queue: VecDeque<_> = binding
+            .into_iter()
+            .filter_map(UnboundWitnessed::merge_many)
+            .collect();
+        while queue.len() >= EVERY_SECOND_LAYER {
+            let chunk = UnboundWitnessed::merge_many(
+                queue
+                    .drain(..EVERY_SECOND_LAYER)
+                    .map(|p| corrupt_unless_leaf(p, corrupt_every_n, self)),
+            )
+            .expect("We should have have a non-empty heap after merging a full chunk.");
+            queue.push_front(chunk);
+        }
+        UnboundWitnessed::merge_many(queue)
+    }
+}
+
+/// Multi-pass merge that corrupts all of its last `len / corrupt_every_n` melds.
+///
+/// Doesn't work: corrupts far too much.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AtEnd;
+
+impl MergeStrategy for AtEnd {
+    fn merge<T: Ord, R: Report<T>>(
+        self,
+        corrupt_every_n: usize,
+        items: Vec<UnboundWitnessed<T, R>>,
+    ) -> Option<UnboundWitnessed<T, R>> {
+        let l = items.len().max(1);
+        // Total = l-1 comparisons.
+        // So we need floor(l / EVERY) corruptions at the end.
+        let end = l - l / corrupt_every_n;
+        let mut queue = VecDeque::from(items);
+        for c in 1_usize.. {
+            let next = match (queue.pop_front(), queue.pop_front()) {
+                (Some(a), Some(b)) => a.meld(b),
+                (a, _) => return a,
+            };
+            queue.push_back(if c > end {
+                next.corrupt(corrupt_every_n, self)
+            } else {
+                next
+            });
+        }
+        unreachable!()
+    }
+}
+
+/// Single left-to-right pass, corrupting after every chunk of `corrupt_every_n`.
+///
+/// Doesn't work: leads to 100% corruption, probably for the same reason that one-pass pairing
+/// heaps have amortised `O(n)` delete-min.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OnePass;
+
+impl MergeStrategy for OnePass {
+    fn merge<T: Ord, R: Report<T>>(
+        self,
+        corrupt_every_n: usize,
+        items: Vec<UnboundWitnessed<T, R>>,
+    ) -> Option<UnboundWitnessed<T, R>> {
+        items
+            .into_iter()
+            .chunks(corrupt_every_n)
+            .into_iter()
+            .fold(None, |acc, chunk| {
+                chain!(
+                    acc.map(|acc: UnboundWitnessed<T, R>| acc.corrupt(corrupt_every_n, self)),
+                    chunk
+                )
+                .reduce(UnboundWitnessed::meld)
+            })
+    }
+}
+
+/// Two-pass pairing, that corrupts whenever the accumulated root has more than
+/// `corrupt_every_n` children.
+///
+/// Doesn't work: leads to 100% corruption.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TwoPassDegree;
+
+impl MergeStrategy for TwoPassDegree {
+    fn merge<T: Ord, R: Report<T>>(
+        self,
+        corrupt_every_n: usize,
+        items: Vec<UnboundWitnessed<T, R>>,
+    ) -> Option<UnboundWitnessed<T, R>> {
+        items
+            .into_iter()
+            .chunks(2)
+            .into_iter()
+            .filter_map(|chunk| chunk.reduce(UnboundWitnessed::meld))
+            .reduce(|a, b| {
+                let acc = a.meld(b);
+                if acc.pairing.children.len() > corrupt_every_n {
+                    acc.corrupt(corrupt_every_n, self)
+                } else {
+                    acc
+                }
+            })
+    }
+}
+
+/// Like [`PassHQueueSimple`], but takes each corrupted chunk apart again: pools the root into a
+/// random child, and puts all the children back into the queue in random order.  Every
+/// corruption buys another chunk of `corrupt_every_n` heaps, that we merge for free at the end.
+///
+/// Doesn't work: corrupts far too much.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PassHQueue;
+
+impl MergeStrategy for PassHQueue {
+    fn merge<T: Ord, R: Report<T>>(
+        self,
+        corrupt_every_n: usize,
+        items: Vec<UnboundWitnessed<T, R>>,
+    ) -> Option<UnboundWitnessed<T, R>> {
+        let mut queue = VecDeque::from(items);
+        let mut free: usize = 1;
+        while queue.len() > free.saturating_mul(corrupt_every_n) {
+            let chunk = UnboundWitnessed::merge_many(queue.drain(..corrupt_every_n))
+                .expect("We should have have a non-empty heap after merging a full chunk.");
+            free += 1;
+            let mut children = chunk.corrupt_apart(|mut children| {
+                children.shuffle(&mut rand::rng());
+                children
+            });
+            children.shuffle(&mut rand::rng());
+            queue.extend(children);
+        }
+        UnboundWitnessed::merge_many(queue)
+    }
+}
+
+/// Like [`PassHQueue`], but merges [`POWER`] of the children of each corrupted chunk, and pools
+/// the root into that merged heap.  Without any free chunks.
+///
+/// Doesn't work: corrupts far too much.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PassHQueuePowerOfN;
+
+/// How many children [`PassHQueuePowerOfN`] merges for the root to pool into.
+const POWER: usize = 10;
+
+impl MergeStrategy for PassHQueuePowerOfN {
+    fn merge<T: Ord, R: Report<T>>(
+        self,
+        corrupt_every_n: usize,
+        items: Vec<UnboundWitnessed<T, R>>,
+    ) -> Option<UnboundWitnessed<T, R>> {
+        let mut queue = VecDeque::from(items);
+        while queue.len() >= corrupt_every_n {
+            let chunk = UnboundWitnessed::merge_many(queue.drain(..corrupt_every_n))
+                .expect("We should have have a non-empty heap after merging a full chunk.");
+            let mut children = chunk.corrupt_apart(|mut children| {
+                children.shuffle(&mut rand::rng());
+                let mix = children.split_off(children.len().saturating_sub(POWER));
+                chain!(UnboundWitnessed::merge_many(mix), children).collect()
+            });
+            children.shuffle(&mut rand::rng());
+            queue.extend(children);
+        }
+        UnboundWitnessed::merge_many(queue)
+    }
+}
+
+/// Picks one of the strategies above at runtime.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Strategy {
+    #[default]
+    PassH,
+    PassHQueueSimple,
+    MultiPassBinary,
+    MultiPassBinaryImplicit,
+    Evenly,
+    TwoPass,
+    TwoPassGrouped,
+    TwoPassGroupedLast,
+    MultiGrouped,
+    MultiGroupedLessGrace,
+    AtEnd,
+    OnePass,
+    TwoPassDegree,
+    PassHQueue,
+    PassHQueuePowerOfN,
+}
+
+impl Strategy {
+    pub const ALL: [Self; 15] = [
+        Self::PassH,
+        Self::PassHQueueSimple,
+        Self::MultiPassBinary,
+        Self::MultiPassBinaryImplicit,
+        Self::Evenly,
+        Self::TwoPass,
+        Self::TwoPassGrouped,
+        Self::TwoPassGroupedLast,
+        Self::MultiGrouped,
+        Self::MultiGroupedLessGrace,
+        Self::AtEnd,
+        Self::OnePass,
+        Self::TwoPassDegree,
+        Self::PassHQueue,
+        Self::PassHQueuePowerOfN,
+    ];
+}
+
+impl MergeStrategy for Strategy {
+    fn merge<T: Ord, R: Report<T>>(
+        self,
+        corrupt_every_n: usize,
+        items: Vec<UnboundWitnessed<T, R>>,
+    ) -> Option<UnboundWitnessed<T, R>> {
+        match self {
+            Self::PassH => PassH.merge(corrupt_every_n, items),
+            Self::PassHQueueSimple => PassHQueueSimple.merge(corrupt_every_n, items),
+            Self::MultiPassBinary => MultiPassBinary.merge(corrupt_every_n, items),
+            Self::MultiPassBinaryImplicit => MultiPassBinaryImplicit.merge(corrupt_every_n, items),
+            Self::Evenly => Evenly.merge(corrupt_every_n, items),
+            Self::TwoPass => TwoPass.merge(corrupt_every_n, items),
+            Self::TwoPassGrouped => TwoPassGrouped.merge(corrupt_every_n, items),
+            Self::TwoPassGroupedLast => TwoPassGroupedLast.merge(corrupt_every_n, items),
+            Self::MultiGrouped => MultiGrouped.merge(corrupt_every_n, items),
+            Self::MultiGroupedLessGrace => MultiGroupedLessGrace.merge(corrupt_every_n, items),
+            Self::AtEnd => AtEnd.merge(corrupt_every_n, items),
+            Self::OnePass => OnePass.merge(corrupt_every_n, items),
+            Self::TwoPassDegree => TwoPassDegree.merge(corrupt_every_n, items),
+            Self::PassHQueue => PassHQueue.merge(corrupt_every_n, items),
+            Self::PassHQueuePowerOfN => PassHQueuePowerOfN.merge(corrupt_every_n, items),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pairing::SoftHeap;
+
+    #[test]
+    fn all_strategies_sort() {
+        let mut items: Vec<u32> = (0..2_000).collect();
+        items.shuffle(&mut rand::rng());
+        for strategy in Strategy::ALL {
+            let mut heap = SoftHeap::with_strategy(4, strategy);
+            heap.extend(items.iter().copied());
+            let mut seen = vec![];
+            while !heap.is_empty() {
+                let (item, corrupted) = heap.heavy_pop();
+                seen.extend(item);
+                seen.extend(corrupted);
+            }
+            seen.sort_unstable();
+            assert_eq!(seen, (0..2_000).collect::<Vec<_>>(), "{strategy:?}");
+        }
+    }
+}
@@ -0,0 +1,645 @@
+// Minimum spanning forests, with random sampling and linear time verification.
+//
+// The randomized linear time algorithm by Karger, Klein and Tarjan, see
+// https://en.wikipedia.org/wiki/Expected_linear_time_MST_algorithm , alternates Borůvka steps
+// with sampling: find the minimum spanning forest `F` of a random half of the edges, throw away
+// all `F`-heavy edges, and recurse on what's left.  Any sample at all gives the right answer: an
+// `F`-heavy edge is the heaviest edge on some cycle, so it can't be in the minimum spanning
+// forest.  The sample only decides how many edges we get to throw away.
+//
+// But it has to be random to throw away enough.  With a random half, only about `2n` of the
+// edges outside of it are `F`-light, in expectation.  A soft heap could hand us roughly the
+// lightest half instead, but then all of the edges outside of it can be `F`-light, eg when the
+// heaviest edges form a long path.  That's no better than the `O((n + m) log n)` comparisons of
+// plain Borůvka.  With random sampling, we take an expected linear number of comparisons.  See
+// the `comparisons` test for how that looks next to plain Borůvka.
+//
+// Throwing away `F`-heavy edges is the verification step, see `f_light`.  That takes a linear
+// number of comparisons, via King's Borůvka trees and Komlós' binary searches.
+//
+// We break ties between equal weights by edge index, so the minimum spanning forest is unique,
+// and every algorithm here finds the same one.
+
+use std::collections::{HashMap, HashSet};
+
+use rand::Rng;
+
+/// Borůvka steps per round, before we sample.  Each step at least halves the number of vertices
+/// that still have edges.
+const BORUVKA_STEPS: usize = 2;
+
+/// Below this many edges, we just run Kruskal.
+const SMALL: usize = 8;
+
+/// An undirected edge between vertices `u` and `v`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Edge<W> {
+    pub u: usize,
+    pub v: usize,
+    pub weight: W,
+}
+
+impl<W> Edge<W> {
+    pub const fn new(u: usize, v: usize, weight: W) -> Self {
+        Self { u, v, weight }
+    }
+
+    const fn as_triple(&self) -> (usize, usize, &W) {
+        (self.u, self.v, &self.weight)
+    }
+}
+
+/// The minimum spanning forest of the graph with vertices `0..n` and `edges`.
+///
+/// Alternates Borůvka steps with random sampling and pruning, see the module comment.  Returns
+/// the forest's edges in input order.  Ties go to the edge that comes first.
+///
+/// Takes an expected `O(n + m)` weight comparisons for `m` edges.
+///
+/// # Panics
+///
+/// Panics if an edge has an endpoint outside of `0..n`.
+#[must_use]
+pub fn minimum_spanning_forest<W: Ord + Clone>(n: usize, edges: &[Edge<W>]) -> Vec<Edge<W>> {
+    let arcs = arcs(n, edges);
+    let ids = msf_ids(edges, n, arcs);
+    in_input_order(edges, ids)
+}
+
+/// The minimum spanning forest via Kruskal's algorithm: sort, and then union-find.
+///
+/// Same answer as [`minimum_spanning_forest`], including ties.
+///
+/// # Panics
+///
+/// Panics if an edge has an endpoint outside of `0..n`.
+#[must_use]
+pub fn kruskal<W: Ord + Clone>(n: usize, edges: &[Edge<W>]) -> Vec<Edge<W>> {
+    let arcs = arcs(n, edges);
+    let ids = kruskal_ids(edges, n, arcs);
+    in_input_order(edges, ids)
+}
+
+/// The minimum spanning forest via Borůvka's algorithm alone: contract everybody's lightest
+/// edge, until no edges are left.
+///
+/// Same answer as [`minimum_spanning_forest`], including ties.  Takes `O(m log n)` comparisons.
+///
+/// # Panics
+///
+/// Panics if an edge has an endpoint outside of `0..n`.
+#[must_use]
+pub fn boruvka<W: Ord + Clone>(n: usize, edges: &[Edge<W>]) -> Vec<Edge<W>> {
+    let (mut n, mut arcs) = (n, arcs(n, edges));
+    let mut forest = vec![];
+    while !arcs.is_empty() {
+        let chosen;
+        (n, arcs, chosen) = boruvka_step(edges, n, arcs);
+        forest.extend(chosen);
+    }
+    in_input_order(edges, forest)
+}
+
+/// Which of `edges` are light with respect to the `forest`.
+///
+/// An edge is `F`-light, if its endpoints are in different trees of the forest, or if its weight
+/// is at most the heaviest weight on the forest path between its endpoints.  Everything else is
+/// `F`-heavy, and can't be in the minimum spanning forest.  Self-loops count as heavy.
+///
+/// Takes a linear number of weight comparisons, `O(n + m)` for `m` edges.
+///
+/// # Panics
+///
+/// Panics if `forest` has a cycle, or if an edge has an endpoint outside of `0..n`.
+#[must_use]
+pub fn f_light<W: Ord>(n: usize, forest: &[Edge<W>], edges: &[Edge<W>]) -> Vec<bool> {
+    let forest: Vec<_> = forest.iter().map(Edge::as_triple).collect();
+    let queries: Vec<_> = edges.iter().map(Edge::as_triple).collect();
+    light_mask(n, &forest, &queries)
+}
+
+/// An edge of a contracted graph.  `id` points into the original edges.
+#[derive(Debug, Clone, Copy)]
+struct Arc {
+    u: usize,
+    v: usize,
+    id: usize,
+}
+
+fn arcs<W>(n: usize, edges: &[Edge<W>]) -> Vec<Arc> {
+    edges
+        .iter()
+        .enumerate()
+        .inspect(|(_, edge)| assert!(edge.u < n && edge.v < n, "endpoint out of range"))
+        .filter(|(_, edge)| edge.u != edge.v)
+        .map(|(id, edge)| Arc {
+            u: edge.u,
+            v: edge.v,
+            id,
+        })
+        .collect()
+}
+
+fn in_input_order<W: Clone>(edges: &[Edge<W>], ids: Vec<usize>) -> Vec<Edge<W>> {
+    let mut in_forest = vec![false; edges.len()];
+    for id in ids {
+        in_forest[id] = true;
+    }
+    edges
+        .iter()
+        .zip(in_forest)
+        .filter(|&(_, in_forest)| in_forest)
+        .map(|(edge, _)| edge.clone())
+        .collect()
+}
+
+/// Weights, with ties broken by index.
+fn key<W>(edges: &[Edge<W>], arc: Arc) -> (&W, usize) {
+    (&edges[arc.id].weight, arc.id)
+}
+
+fn msf_ids<W: Ord>(edges: &[Edge<W>], n: usize, arcs: Vec<Arc>) -> Vec<usize> {
+    let mut forest = vec![];
+    let (mut n, mut arcs) = (n, arcs);
+    for _ in 0..BORUVKA_STEPS {
+        if arcs.is_empty() {
+            return forest;
+        }
+        let chosen;
+        (n, arcs, chosen) = boruvka_step(edges, n, arcs);
+        forest.extend(chosen);
+    }
+    if arcs.len() <= SMALL {
+        forest.extend(kruskal_ids(edges, n, arcs));
+        return forest;
+    }
+
+    let sample = random_half(&arcs);
+    let in_sample_forest: HashSet<usize> = msf_ids(edges, n, sample.clone()).into_iter().collect();
+    let sample_forest: Vec<_> = sample
+        .into_iter()
+        .filter(|arc| in_sample_forest.contains(&arc.id))
+        .map(|arc| (arc.u, arc.v, key(edges, arc)))
+        .collect();
+    let queries: Vec<_> = arcs
+        .iter()
+        .map(|&arc| (arc.u, arc.v, key(edges, arc)))
+        .collect();
+    let light = light_mask(n, &sample_forest, &queries);
+    let light_arcs = arcs
+        .into_iter()
+        .zip(light)
+        .filter_map(|(arc, light)| light.then_some(arc))
+        .collect();
+    forest.extend(msf_ids(edges, n, light_arcs));
+    forest
+}
+
+/// Every vertex picks its lightest edge, and we contract all of them.
+///
+/// Returns the number of vertices left with any edges, the contracted edges without self-loops
+/// and with only the lightest of parallel edges, and the ids of the picked edges.
+fn boruvka_step<W: Ord>(
+    edges: &[Edge<W>],
+    n: usize,
+    arcs: Vec<Arc>,
+) -> (usize, Vec<Arc>, Vec<usize>) {
+    let mut lightest: Vec<Option<Arc>> = vec![None; n];
+    for &arc in &arcs {
+        for x in [arc.u, arc.v] {
+            if lightest[x].is_none_or(|best| key(edges, arc) < key(edges, best)) {
+                lightest[x] = Some(arc);
+            }
+        }
+    }
+    let mut components = UnionFind::new(n);
+    let mut chosen = vec![];
+    for arc in lightest.into_iter().flatten() {
+        // Both endpoints might have picked the same edge.
+        if components.union(arc.u, arc.v) {
+            chosen.push(arc.id);
+        }
+    }
+
+    let mut labels: Vec<Option<usize>> = vec![None; n];
+    let mut next_label = 0;
+    let mut label = |x: usize, components: &mut UnionFind| {
+        let root = components.find(x);
+        *labels[root].get_or_insert_with(|| {
+            next_label += 1;
+            next_label - 1
+        })
+    };
+    let mut parallel: HashMap<(usize, usize), Arc> = HashMap::new();
+    for arc in arcs {
+        if components.find(arc.u) == components.find(arc.v) {
+            continue;
+        }
+        let (u, v) = (label(arc.u, &mut components), label(arc.v, &mut components));
+        let arc = Arc { u, v, id: arc.id };
+        parallel
+            .entry((u.min(v), u.max(v)))
+            .and_modify(|best| {
+                if key(edges, arc) < key(edges, *best) {
+                    *best = arc;
+                }
+            })
+            .or_insert(arc);
+    }
+    (next_label, parallel.into_values().collect(), chosen)
+}
+
+/// Every arc goes into the sample with probability one half, like in KKT.
+fn random_half(arcs: &[Arc]) -> Vec<Arc> {
+    let mut rng = rand::rng();
+    arcs.iter()
+        .copied()
+        .filter(|_| rng.random_bool(0.5))
+        .collect()
+}
+
+fn kruskal_ids<W: Ord>(edges: &[Edge<W>], n: usize, mut arcs: Vec<Arc>) -> Vec<usize> {
+    arcs.sort_unstable_by(|&a, &b| key(edges, a).cmp(&key(edges, b)));
+    let mut components = UnionFind::new(n);
+    arcs.into_iter()
+        .filter(|arc| components.union(arc.u, arc.v))
+        .map(|arc| arc.id)
+        .collect()
+}
+
+/// For every query `(u, v, k)`, whether `k` is at most the biggest key on the `forest` path
+/// from `u` to `v`, or whether there's no such path.
+///
+/// Uses a linear number of comparisons between keys, like in King's 'A simpler minimum spanning
+/// tree verification algorithm': the paths in the Borůvka tree of the forest have the same
+/// biggest keys as the paths in the forest.  All leaves of a Borůvka tree have the same depth,
+/// and every inner node has at least two children.  On such trees, Komlós' trick answers all the
+/// queries with `O(n + m)` comparisons: every node only needs one binary search.
+fn light_mask<K: Ord + Copy>(
+    n: usize,
+    forest: &[(usize, usize, K)],
+    queries: &[(usize, usize, K)],
+) -> Vec<bool> {
+    let tree = BoruvkaTree::new(n, forest);
+
+    // Split every query into two halves, from each endpoint up to their lowest common ancestor.
+    // `wanted[x]` are the depths of the ancestors that the halves through `x` go up to.
+    let mut wanted: Vec<Vec<usize>> = vec![vec![]; tree.parent.len()];
+    let lcas: Vec<Option<usize>> = queries
+        .iter()
+        .map(|&(u, v, _)| {
+            let lca = tree.lca(u, v)?;
+            (u != v).then(|| {
+                wanted[u].push(tree.depth[lca]);
+                wanted[v].push(tree.depth[lca]);
+                lca
+            })
+        })
+        .collect();
+    // Children come before their parents, and every half through a child that goes higher than
+    // the parent also goes through the parent.
+    for x in 0..tree.parent.len() {
+        wanted[x].sort_unstable();
+        wanted[x].dedup();
+        if let Some(p) = tree.parent[x] {
+            let mut higher: Vec<usize> = wanted[x]
+                .iter()
+                .copied()
+                .filter(|&d| d < tree.depth[p])
+                .collect();
+            wanted[p].append(&mut higher);
+        }
+    }
+
+    // `heaviest[x][i]` is the biggest key from `x` up to its ancestor at depth `wanted[x][i]`.
+    // Going up further can only make it bigger, so every list is sorted descending.
+    let mut heaviest: Vec<Vec<K>> = vec![vec![]; tree.parent.len()];
+    for x in (0..tree.parent.len()).rev() {
+        let (Some(p), Some(k)) = (tree.parent[x], tree.key[x]) else {
+            continue;
+        };
+        let from_parent = wanted[x].iter().filter(|&&d| d < tree.depth[p]).map(|d| {
+            heaviest[p][wanted[p]
+                .binary_search(d)
+                .expect("the parent wants every depth above it that its children want")]
+        });
+        let mut mine: Vec<K> = from_parent.collect();
+        // The only comparisons between keys: one binary search per node.
+        let lighter = mine.partition_point(|&h| h >= k);
+        mine[lighter..].fill(k);
+        if wanted[x].last() == Some(&tree.depth[p]) {
+            mine.push(k);
+        }
+        heaviest[x] = mine;
+    }
+
+    let up_to = |x: usize, lca: usize| {
+        let i = wanted[x]
+            .binary_search(&tree.depth[lca])
+            .expect("we asked for it");
+        heaviest[x][i]
+    };
+    queries
+        .iter()
+        .zip(lcas)
+        .map(|(&(u, v, k), lca)| match lca {
+            None => tree.lca(u, v).is_none(),
+            Some(lca) => k <= up_to(u, lca).max(up_to(v, lca)),
+        })
+        .collect()
+}
+
+/// The tree of components that Borůvka's algorithm builds, when it runs on a forest.
+///
+/// The leaves `0..n` are the vertices.  Every other node is a component from some round, whose
+/// children are the components from the round before, that it's made of.  A child's `key` is the
+/// key of the edge it picked in its round.
+struct BoruvkaTree<K> {
+    parent: Vec<Option<usize>>,
+    key: Vec<Option<K>>,
+    depth: Vec<usize>,
+}
+
+impl<K: Ord + Copy> BoruvkaTree<K> {
+    /// Takes a linear number of comparisons: every round of Borůvka at least halves the number
+    /// of edges left in a forest.
+    fn new(n: usize, forest: &[(usize, usize, K)]) -> Self {
+        let mut components = UnionFind::new(n);
+        for &(u, v, _) in forest {
+            assert!(components.union(u, v), "forest has a cycle");
+        }
+
+        let mut parent = vec![None; n];
+        let mut key = vec![None; n];
+        // `node[c]` is the tree node of component `c` in the current round.
+        let mut node: Vec<usize> = (0..n).collect();
+        let mut edges = forest.to_vec();
+        while !edges.is_empty() {
+            let rounds_n = node.len();
+            let mut lightest: Vec<Option<(usize, usize, K)>> = vec![None; rounds_n];
+            for &edge in &edges {
+                for c in [edge.0, edge.1] {
+                    if lightest[c].is_none_or(|best| edge.2 < best.2) {
+                        lightest[c] = Some(edge);
+                    }
+                }
+            }
+            let mut components = UnionFind::new(rounds_n);
+            for edge in lightest.iter().flatten() {
+                components.union(edge.0, edge.1);
+            }
+            // Components without edges are whole trees, and don't get a parent.
+            let mut label: Vec<Option<usize>> = vec![None; rounds_n];
+            let mut next_node = vec![];
+            for (c, edge) in lightest.iter().enumerate() {
+                let Some(edge) = edge else {
+                    continue;
+                };
+                let root = components.find(c);
+                let l = *label[root].get_or_insert_with(|| {
+                    next_node.push(parent.len());
+                    parent.push(None);
+                    key.push(None);
+                    next_node.len() - 1
+                });
+                parent[node[c]] = Some(next_node[l]);
+                key[node[c]] = Some(edge.2);
+            }
+            edges = edges
+                .into_iter()
+                .filter_map(|(u, v, k)| {
+                    let (u, v) = (components.find(u), components.find(v));
+                    (u != v).then(|| (label[u].unwrap(), label[v].unwrap(), k))
+                })
+                .collect();
+            node = next_node;
+        }
+
+        // Parents come after their children.
+        let mut depth = vec![0; parent.len()];
+        for x in (0..parent.len()).rev() {
+            if let Some(p) = parent[x] {
+                depth[x] = depth[p] + 1;
+            }
+        }
+        Self { parent, key, depth }
+    }
+
+    /// The lowest common ancestor of the leaves `u` and `v`, if they are in the same tree.
+    ///
+    /// All leaves of a tree have the same depth, so we can walk up in lockstep.  That doesn't
+    /// compare any keys.
+    fn lca(&self, mut u: usize, mut v: usize) -> Option<usize> {
+        while u != v {
+            u = self.parent[u]?;
+            v = self.parent[v]?;
+        }
+        Some(u)
+    }
+}
+
+/// Disjoint sets with path halving and union by size.
+struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(n: usize) -> Self {
+        Self {
+            parent: (0..n).collect(),
+            size: vec![1; n],
+        }
+    }
+
+    fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    /// Returns whether `a` and `b` were in different sets.
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        if self.size[a] < self.size[b] {
+            (a, b) = (b, a);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::Counted;
+    use proptest::prelude::{prop_assert, prop_assert_eq, proptest, Strategy};
+    use rand::Rng;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn graphs() -> impl Strategy<Value = (usize, Vec<Edge<u8>>)> {
+        (1..60_usize).prop_flat_map(|n| {
+            let edge = (0..n, 0..n, 0..20_u8).prop_map(|(u, v, w)| Edge::new(u, v, w));
+            (
+                proptest::strategy::Just(n),
+                proptest::collection::vec(edge, 0..300),
+            )
+        })
+    }
+
+    /// Whether `edge` is light with respect to `forest`, by depth first search.
+    fn naive_light<W: Ord + Copy>(forest: &[Edge<W>], edge: &Edge<W>) -> bool {
+        let mut adjacent: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (i, e) in forest.iter().enumerate() {
+            adjacent.entry(e.u).or_default().push(i);
+            adjacent.entry(e.v).or_default().push(i);
+        }
+        let mut todo = vec![(edge.u, usize::MAX, None)];
+        while let Some((x, from, heaviest)) = todo.pop() {
+            if x == edge.v {
+                return heaviest.is_some_and(|heaviest| edge.weight <= heaviest);
+            }
+            for &i in adjacent.get(&x).into_iter().flatten() {
+                let e = &forest[i];
+                if i != from {
+                    let y = if e.u == x { e.v } else { e.u };
+                    todo.push((y, i, heaviest.max(Some(e.weight))));
+                }
+            }
+        }
+        // Different trees.
+        true
+    }
+
+    proptest! {
+        #[test]
+        fn matches_kruskal((n, edges) in graphs()) {
+            let expected = kruskal(n, &edges);
+            prop_assert_eq!(minimum_spanning_forest(n, &edges), expected.clone());
+            prop_assert_eq!(boruvka(n, &edges), expected);
+        }
+
+        #[test]
+        fn light_edges_match_naive((n, edges) in graphs()) {
+            let half = edges.len() / 2;
+            let forest = kruskal(n, &edges[..half]);
+            for (edge, light) in edges.iter().zip(f_light(n, &forest, &edges)) {
+                prop_assert_eq!(light, naive_light(&forest, edge));
+            }
+            // Every edge of the minimum spanning forest is light.
+            let all = kruskal(n, &edges);
+            prop_assert!(f_light(n, &forest, &all).into_iter().all(|light| light));
+        }
+    }
+
+    #[test]
+    fn linear_comparisons() {
+        const N: usize = 20_000;
+        const M: usize = 200_000;
+        let counter = Rc::new(Cell::new(0));
+        let mut rng = rand::rng();
+        let mut weight = || Counted::new(rng.random_range(0..1_000_u32), &counter);
+        let forest: Vec<_> = (1..N)
+            .map(|v| Edge::new(rand::rng().random_range(0..v), v, weight()))
+            .collect();
+        let edges: Vec<_> = (0..M)
+            .map(|_| {
+                let (u, v) = (
+                    rand::rng().random_range(0..N),
+                    rand::rng().random_range(0..N),
+                );
+                Edge::new(u, v, weight())
+            })
+            .collect();
+        let light = f_light(N, &forest, &edges);
+        assert!(counter.get() <= 4 * (N + M), "{}", counter.get());
+
+        let plain = |edges: &[Edge<Counted<u32>>]| -> Vec<Edge<u32>> {
+            edges
+                .iter()
+                .map(|edge| Edge::new(edge.u, edge.v, *edge.weight.value()))
+                .collect()
+        };
+        let (forest, edges) = (plain(&forest), plain(&edges));
+        for (edge, light) in edges.iter().zip(light).step_by(1_000) {
+            assert_eq!(light, naive_light(&forest, edge));
+        }
+    }
+
+    /// Comparisons that [`minimum_spanning_forest`] and [`boruvka`] take on the same graph.
+    fn count_comparisons(n: usize, edges: Vec<(usize, usize, u32)>) -> (usize, usize) {
+        let counter = Rc::new(Cell::new(0));
+        let edges: Vec<_> = edges
+            .into_iter()
+            .map(|(u, v, weight)| Edge::new(u, v, Counted::new(weight, &counter)))
+            .collect();
+        let _ = minimum_spanning_forest(n, &edges);
+        let pruned = counter.replace(0);
+        let _ = boruvka(n, &edges);
+        (pruned, counter.get())
+    }
+
+    #[test]
+    fn comparisons() {
+        const N: usize = 1 << 16;
+        const M: usize = 1 << 18;
+        let mut rng = rand::rng();
+        let mut noise = |weights: std::ops::Range<u32>| -> Vec<_> {
+            (0..M)
+                .map(|_| {
+                    let (u, v) = (rng.random_range(0..N), rng.random_range(0..N));
+                    (u, v, rng.random_range(weights.clone()))
+                })
+                .collect()
+        };
+
+        // Light edges that pair up the vertices, then the pairs, and so on, so that every
+        // Borůvka step only halves the vertices.  Plus heavy random edges, that Borůvka has to
+        // look at again in every step, but that pruning gets rid of.
+        let mut edges: Vec<_> = (0..N.ilog2())
+            .flat_map(|level| {
+                (0..N)
+                    .step_by(2 << level)
+                    .map(move |u| (u, u + (1 << level), level))
+            })
+            .collect();
+        edges.extend(noise(100..1_000));
+        let m = edges.len();
+        let (pruned, plain) = count_comparisons(N, edges);
+        // In practice, that's about 8 and 11 comparisons per vertex and edge.
+        assert!(pruned <= 10 * (N + m), "{pruned}");
+        assert!(pruned * 5 <= plain * 4, "{pruned} {plain}");
+
+        // A long path of heavy edges: none of them make it into a lightest half, but that
+        // doesn't matter for a random sample.
+        let mut edges = noise(0..1_000);
+        edges.extend((1..N).map(|v| (v - 1, v, 1_000)));
+        let m = edges.len();
+        let (pruned, _) = count_comparisons(N, edges);
+        assert!(pruned <= 10 * (N + m), "{pruned}");
+    }
+
+    #[test]
+    fn big_random_graph() {
+        let n = 5_000;
+        let mut rng = rand::rng();
+        let mut edges: Vec<_> = (0..50_000)
+            .map(|_| {
+                let (u, v) = (rng.random_range(0..n), rng.random_range(0..n));
+                Edge::new(u, v, rng.random_range(0..1_000_u32))
+            })
+            .collect();
+        // Make it connected.
+        edges.extend((1..n).map(|v| Edge::new(v - 1, v, 1_000)));
+        let forest = minimum_spanning_forest(n, &edges);
+        assert_eq!(forest.len(), n - 1);
+        assert_eq!(forest, kruskal(n, &edges));
+    }
+}
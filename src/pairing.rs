@@ -1,10 +1,14 @@
 // Soft heaps based on pairing heaps.
 // We do min-heaps by default.
 
-use itertools::{chain, Itertools};
-use std::ops::Add;
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt::{self, Debug, Display};
+use std::ops::{Deref, DerefMut};
 use std::{collections::VecDeque, mem};
 
+use crate::merge_strategy::{MergeStrategy, PassH};
+use crate::tree::{clone_tree, cmp_trees, eq_trees, fmt_tree, fmt_trees, Tree};
 use crate::witness_set::{Witnessed, WitnessedSet};
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
@@ -35,17 +39,55 @@ impl<T> Pool<T> {
     }
 }
 
+/// Where [`UnboundWitnessed`] keeps the items it corrupts, until somebody reports them.
+///
+/// A [`WitnessedSet`] hides them in the witnessed set of whoever wins the next meld, so they only
+/// get reported when that node gets deleted.  [`SoftHeap::pop_min_events`] uses its own
+/// implementation, that reports them right away, together with the key they got pooled under.
+pub trait Report<T>: Default {
+    /// How many items are waiting to be reported.
+    fn count(&self) -> usize;
+    fn append(&mut self, other: Self);
+    /// The witnessed set of a node that goes away.  Everything it was hiding is due now.
+    fn witness(&mut self, witnessed: WitnessedSet<T>);
+    /// `item` just got pooled under the key `pool`.
+    fn pool(&mut self, item: T, pool: &T);
+    /// We belong to the loser of a meld.  Hides whatever should wait in the `witnessed` set of
+    /// the winner, and returns the rest.
+    #[must_use]
+    fn hide(self, witnessed: &mut WitnessedSet<T>) -> Self;
+}
+
+impl<T> Report<T> for WitnessedSet<T> {
+    fn count(&self) -> usize {
+        self.count
+    }
+    fn append(&mut self, other: Self) {
+        self.extend(other);
+    }
+    fn witness(&mut self, witnessed: WitnessedSet<T>) {
+        self.extend(witnessed);
+    }
+    fn pool(&mut self, item: T, _pool: &T) {
+        self.add_child(Witnessed::singleton(item));
+    }
+    fn hide(self, witnessed: &mut WitnessedSet<T>) -> Self {
+        witnessed.extend(self);
+        Self::default()
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
-pub struct UnboundWitnessed<T> {
+pub struct UnboundWitnessed<T, R = WitnessedSet<T>> {
     pub pairing: Pairing<T>,
-    pub to_be_witnessed: WitnessedSet<T>,
+    pub to_be_witnessed: R,
 }
 
-impl<T> From<Pairing<T>> for UnboundWitnessed<T> {
+impl<T, R: Default> From<Pairing<T>> for UnboundWitnessed<T, R> {
     fn from(pairing: Pairing<T>) -> Self {
         Self {
             pairing,
-            to_be_witnessed: WitnessedSet::default(),
+            to_be_witnessed: R::default(),
         }
     }
 }
@@ -63,7 +105,9 @@ impl<T: Ord> UnboundWitnessed<T> {
             (None, vec![])
         }
     }
+}
 
+impl<T: Ord, R: Report<T>> UnboundWitnessed<T, R> {
     #[must_use]
     pub fn meld(self, other: Self) -> Self {
         let (mut a, b) = if self.pairing.key.item <= other.pairing.key.item {
@@ -71,16 +115,18 @@ impl<T: Ord> UnboundWitnessed<T> {
         } else {
             (other, self)
         };
-        a.pairing.witnessed.extend(b.to_be_witnessed);
+        let rest = b.to_be_witnessed.hide(&mut a.pairing.witnessed);
+        a.to_be_witnessed.append(rest);
         a.pairing.children.push(b.pairing);
         a
     }
 
     #[must_use]
-    pub fn pop_min(
+    pub fn pop_min<M: MergeStrategy>(
         self,
         corrupt_every_n: usize,
-    ) -> (Option<Pairing<T>>, Option<T>, WitnessedSet<T>) {
+        strategy: M,
+    ) -> (Option<Pairing<T>>, Option<T>, R) {
         let UnboundWitnessed {
             pairing:
                 Pairing {
@@ -103,14 +149,14 @@ impl<T: Ord> UnboundWitnessed<T> {
                 if let Some(UnboundWitnessed {
                     to_be_witnessed: tbc,
                     pairing,
-                }) = Self::merge_children(corrupt_every_n, children)
+                }) = Self::merge_children(corrupt_every_n, strategy, children.into_vec())
                 {
                     // This might need to change?  TODO: we always need to do this.
-                    to_be_witnessed.extend(witnessed);
-                    to_be_witnessed.extend(tbc);
+                    to_be_witnessed.witness(witnessed);
+                    to_be_witnessed.append(tbc);
                     Some(pairing)
                 } else {
-                    to_be_witnessed.extend(witnessed);
+                    to_be_witnessed.witness(witnessed);
                     None
                 },
                 Some(item),
@@ -120,10 +166,11 @@ impl<T: Ord> UnboundWitnessed<T> {
         (new_me, deleted_item, to_be_witnessed)
     }
 
-    pub fn heavy_pop_min(
+    pub fn heavy_pop_min<M: MergeStrategy>(
         self,
         corrupt_every_n: usize,
-    ) -> (Option<Pairing<T>>, Pool<T>, WitnessedSet<T>) {
+        strategy: M,
+    ) -> (Option<Pairing<T>>, Pool<T>, R) {
         let UnboundWitnessed {
             pairing:
                 Pairing {
@@ -133,14 +180,14 @@ impl<T: Ord> UnboundWitnessed<T> {
                 },
             mut to_be_witnessed,
         } = self;
-        to_be_witnessed.extend(witnessed);
+        to_be_witnessed.witness(witnessed);
 
-        let new_me = Self::merge_children(corrupt_every_n, children).map(
+        let new_me = Self::merge_children(corrupt_every_n, strategy, children.into_vec()).map(
             |UnboundWitnessed {
                  to_be_witnessed: tbc,
                  pairing,
              }| {
-                to_be_witnessed.extend(tbc);
+                to_be_witnessed.append(tbc);
                 pairing
             },
         );
@@ -158,35 +205,29 @@ impl<T: Ord> UnboundWitnessed<T> {
         }
     }
 
+    /// Merges the children of a deleted node into one heap, with the help of `strategy`.
     #[must_use]
-    pub fn merge_children_pass_h(corrupt_every_n: usize, items: Vec<Pairing<T>>) -> Option<Self> {
-        let mut items = items.into_iter().map(Self::from).collect::<Vec<_>>();
-
-        let start = items
-            .len()
-            .add(1)
-            .next_multiple_of(corrupt_every_n)
-            .saturating_sub(corrupt_every_n);
-
-        assert_eq!(0, start % corrupt_every_n);
-        assert!(items.len() - start <= corrupt_every_n);
-
-        let last = Self::merge_many(items.drain(start..));
-        let binding = items.into_iter().chunks(corrupt_every_n);
-        let chunked = binding
-            .into_iter()
-            .filter_map(Self::merge_many)
-            .map(|x| x.corrupt(corrupt_every_n));
-        Self::merge_many(chain!(chunked, last.into_iter()))
-    }
-
-    #[must_use]
-    pub fn merge_children(corrupt_every_n: usize, children: Vec<Pairing<T>>) -> Option<Self> {
-        Self::merge_children_pass_h(corrupt_every_n, children)
+    pub fn merge_children<M: MergeStrategy>(
+        corrupt_every_n: usize,
+        strategy: M,
+        children: Vec<Pairing<T>>,
+    ) -> Option<Self> {
+        strategy.merge(
+            corrupt_every_n,
+            children.into_iter().map(Self::from).collect(),
+        )
     }
 
+    /// Pools the root into the merged heap of its children.
+    ///
+    /// The root's item goes into `to_be_witnessed`, see [`Report::pool`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node has no children: there's nothing to pool into.  Strategies have to
+    /// make sure that they only corrupt merged heaps.
     #[must_use]
-    pub fn corrupt(self, corrupt_every_n: usize) -> Self {
+    pub fn corrupt<M: MergeStrategy>(self, corrupt_every_n: usize, strategy: M) -> Self {
         // TODO(Matthias): this is like a heavy pop-min, so we should unify?  Maybe..
         let Self {
             pairing:
@@ -200,12 +241,12 @@ impl<T: Ord> UnboundWitnessed<T> {
         if let Some(Self {
             pairing,
             mut to_be_witnessed,
-        }) = Self::merge_children(corrupt_every_n, children)
+        }) = Self::merge_children(corrupt_every_n, strategy, children.into_vec())
         {
             // assert!(key.item <= pairing.key.item);
-            tbw_c.extend(witnessed);
-            tbw_c.add_child(Witnessed::singleton(key.item));
-            to_be_witnessed.extend(tbw_c);
+            tbw_c.witness(witnessed);
+            tbw_c.pool(key.item, &pairing.key.item);
+            to_be_witnessed.append(tbw_c);
             Self {
                 to_be_witnessed,
                 pairing: Pairing {
@@ -218,28 +259,227 @@ impl<T: Ord> UnboundWitnessed<T> {
             unreachable!(
                 "This should never happen, we should always have at least one child to corrupt."
             );
-            // Well, we have no children, so we can't actually corrupt anything.
-            // This shouldn't happen.
-            // Self {
-            //     pairing: Pairing::from(key),
-            //     to_be_witnessed: tbw_c,
-            // }
+        }
+    }
+
+    /// Like [`UnboundWitnessed::corrupt`], but doesn't merge the children back together.
+    ///
+    /// `arrange` gets the children as separate heaps, and can reorder or merge them as it
+    /// likes.  Then we pool the root into the first heap that `arrange` hands back.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `arrange` hands back no heaps, eg because the node has no children.
+    #[must_use]
+    pub fn corrupt_apart(self, arrange: impl FnOnce(Vec<Self>) -> Vec<Self>) -> Vec<Self> {
+        let Self {
+            pairing:
+                Pairing {
+                    key,
+                    children,
+                    witnessed,
+                },
+            to_be_witnessed: mut tbw_c,
+        } = self;
+        let mut children = arrange(children.into_vec().into_iter().map(Self::from).collect());
+        let Some(first) = children.first_mut() else {
+            unreachable!(
+                "This should never happen, we should always have at least one child to corrupt."
+            );
+        };
+        tbw_c.witness(witnessed);
+        tbw_c.pool(key.item, &first.pairing.key.item);
+        first.to_be_witnessed.append(tbw_c);
+        first.pairing.key.count += key.count + 1;
+        children
+    }
+}
+
+/// The children of a [`Pairing`] node.
+///
+/// Derefs to a plain `Vec`, but dropping it tears down the whole subtree with an explicit
+/// stack.  The derived drop would recurse once per level, and deep heaps would overflow the
+/// stack.  Same for `Clone`, `PartialEq`, `PartialOrd` and `Debug`, see `tree.rs`.
+pub struct Children<T>(Vec<Pairing<T>>);
+
+impl<T> Children<T> {
+    #[must_use]
+    pub fn into_vec(mut self) -> Vec<Pairing<T>> {
+        mem::take(&mut self.0)
+    }
+}
+
+impl<T> Default for Children<T> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<T> From<Vec<Pairing<T>>> for Children<T> {
+    fn from(children: Vec<Pairing<T>>) -> Self {
+        Self(children)
+    }
+}
+
+impl<T> Deref for Children<T> {
+    type Target = Vec<Pairing<T>>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Children<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> IntoIterator for Children<T> {
+    type Item = Pairing<T>;
+    type IntoIter = std::vec::IntoIter<Pairing<T>>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_vec().into_iter()
+    }
+}
+
+impl<T> Drop for Children<T> {
+    fn drop(&mut self) {
+        let mut todo = mem::take(&mut self.0);
+        while let Some(mut node) = todo.pop() {
+            // `node` gets dropped at the end of this iteration, but without any children left.
+            todo.append(&mut node.children.0);
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+// Clone, PartialEq, PartialOrd and Debug are written by hand, to avoid recursion, see `tree.rs`.
 pub struct Pairing<T> {
     pub key: Pool<T>,
     pub witnessed: WitnessedSet<T>,
-    pub children: Vec<Pairing<T>>,
+    pub children: Children<T>,
+}
+
+impl<T> Tree for Pairing<T> {
+    fn children(&self) -> &[Self] {
+        &self.children.0
+    }
+    fn children_mut(&mut self) -> &mut Vec<Self> {
+        &mut self.children.0
+    }
+}
+
+/// Clones everything but the children.
+fn clone_node<T: Clone>(node: &Pairing<T>) -> Pairing<T> {
+    Pairing {
+        key: node.key.clone(),
+        witnessed: node.witnessed.clone(),
+        children: Children(Vec::with_capacity(node.children.len())),
+    }
+}
+
+/// Compares everything but the children.
+fn eq_node<T: PartialEq>(a: &Pairing<T>, b: &Pairing<T>) -> bool {
+    a.key == b.key && a.witnessed == b.witnessed
+}
+
+/// Orders by everything but the children.
+fn cmp_node<T: PartialOrd>(a: &Pairing<T>, b: &Pairing<T>) -> Option<Ordering> {
+    match a.key.partial_cmp(&b.key)? {
+        Ordering::Equal => a.witnessed.partial_cmp(&b.witnessed),
+        unequal => Some(unequal),
+    }
+}
+
+/// Everything before the list of children.
+fn fmt_open<T: Debug>(node: &Pairing<T>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+        f,
+        "Pairing {{ key: {:?}, witnessed: {:?}, children: Children(",
+        node.key, node.witnessed
+    )
+}
+
+impl<T: Clone> Clone for Pairing<T> {
+    fn clone(&self) -> Self {
+        clone_tree(self, clone_node)
+    }
+}
+
+impl<T: Clone> Clone for Children<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.iter().map(Pairing::clone).collect())
+    }
+}
+
+impl<T: PartialEq> PartialEq for Pairing<T> {
+    fn eq(&self, other: &Self) -> bool {
+        eq_trees(
+            std::slice::from_ref(self),
+            std::slice::from_ref(other),
+            eq_node,
+        )
+    }
+}
+
+impl<T: Eq> Eq for Pairing<T> {}
+
+impl<T: PartialEq> PartialEq for Children<T> {
+    fn eq(&self, other: &Self) -> bool {
+        eq_trees(&self.0, &other.0, eq_node)
+    }
+}
+
+impl<T: Eq> Eq for Children<T> {}
+
+impl<T: PartialOrd> PartialOrd for Pairing<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        cmp_trees(
+            std::slice::from_ref(self),
+            std::slice::from_ref(other),
+            cmp_node,
+        )
+    }
+}
+
+impl<T: Ord> Ord for Pairing<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.partial_cmp(other)
+            .expect("total orders compare everything")
+    }
+}
+
+impl<T: PartialOrd> PartialOrd for Children<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        cmp_trees(&self.0, &other.0, cmp_node)
+    }
+}
+
+impl<T: Ord> Ord for Children<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.partial_cmp(other)
+            .expect("total orders compare everything")
+    }
+}
+
+impl<T: Debug> Debug for Pairing<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_tree(self, f, fmt_open, ") }")
+    }
+}
+
+impl<T: Debug> Debug for Children<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Children(")?;
+        fmt_trees(&self.0, f, fmt_open, ") }")?;
+        f.write_str(")")
+    }
 }
 
 impl<T> From<Pool<T>> for Pairing<T> {
     fn from(key: Pool<T>) -> Self {
         Self {
             key,
-            children: vec![],
+            children: Children::default(),
             witnessed: WitnessedSet::default(),
         }
     }
@@ -250,75 +490,26 @@ impl<T> Pairing<T> {
         Self::from(Pool::new(item))
     }
 
+    /// All nodes of the tree, in pre-order.  Uses an explicit stack instead of recursion.
+    pub fn nodes(&self) -> impl Iterator<Item = &Self> {
+        let mut todo = vec![self];
+        std::iter::from_fn(move || {
+            let node = todo.pop()?;
+            todo.extend(node.children.iter().rev());
+            Some(node)
+        })
+    }
+
     pub fn count_corrupted(&self) -> usize {
-        self.key.count
-            + self
-                .children
-                .iter()
-                .map(Pairing::count_corrupted)
-                .sum::<usize>()
+        self.nodes().map(|node| node.key.count).sum()
     }
 
     pub fn count_uncorrupted(&self) -> usize {
-        1 + self
-            .children
-            .iter()
-            .map(Pairing::count_uncorrupted)
-            .sum::<usize>()
+        self.nodes().count()
     }
 
     pub fn count_delayed_corruption(&self) -> usize {
-        self.witnessed.count
-            + self
-                .children
-                .iter()
-                .map(Pairing::count_delayed_corruption)
-                .sum::<usize>()
-    }
-}
-
-// const BOUND: usize = 2;
-// TODO: let's worry about whether we really need Clone later.  For now, this is simplest.
-impl<T: Ord + Clone> Pairing<T> {
-    #[must_use]
-    pub fn merge_children_late(_items: Vec<Self>) -> (Option<Self>, WitnessedSet<T>) {
-        todo!()
-        // // This should give =< 1/3 corruption.
-        // let mut total_work: usize = items.len();
-
-        // if let Some(pairing) = Self::merge_many(items) {
-        //     const FIXED_CORRUPT_EVERY_N: usize = 4;
-        //     let mut work_discharged = CORRUPT_EVERY_N;
-
-        //     let mut candidates: Vec<T> = vec![];
-        //     let root = RefCell::new(pairing);
-        //     let mut inner_heap: SoftHeap<4, RefCell<Pairing<T>>> =
-        //         SoftHeap::singleton(root.clone());
-        //     while work_discharged < total_work {
-        //         let (new_heap, mut nodes) = inner_heap.pop_min_combined();
-        //         candidates.extend(nodes.iter().map(|node| node.borrow().key.item.clone()));
-        //         inner_heap = new_heap;
-        //         for node in &mut nodes {
-        //             if node.borrow().children.len() > 2 {
-        //                 todo!();
-        //                 // let my_children = mem::take(&mut node.borrow_mut().children);
-        //                 // total_work += my_children.len();
-        //                 // node.borrow_mut()
-        //                 //     .children
-        //                 //     .extend(Self::merge_many(my_children));
-        //             }
-        //             // inner_heap.extend(node.borrow_mut().children.iter_mut().map(RefCell::new));
-        //             for child in node.borrow_mut().children.iter_mut() {
-        //                 inner_heap.insert(RefCell::new(child));
-        //             }
-        //         }
-
-        //         work_discharged += FIXED_CORRUPT_EVERY_N;
-        //     }
-        //     return todo!();
-        // }
-        // // We have no items, so we can't merge anything.
-        // (None, WitnessedSet::default())
+        self.nodes().map(|node| node.witnessed.count).sum()
     }
 }
 
@@ -420,458 +611,153 @@ impl<T: Ord> Pairing<T> {
         }
     }
 
-    /*
-    pub fn merge_children_multi_pass_binary_implicit(
-        mut items: Vec<Self>,
-        corrupted: &mut Vec<T>,
-    ) -> Option<Self> {
-        let mut counter: usize = 0;
-        while items.len() > 1 {
-            let len = items.len();
-            let binding = items.into_iter().chunks(2);
-            let chunked = binding
-                .into_iter()
-                .filter_map(|chunk| chunk.reduce(Self::meld));
-            items = chunked.collect();
-            if len % 4 == 3 {
-                // Odd to even!
-                assert!(len % 2 == 1);
-                assert!(items.len() % 2 == 0);
-                counter += 1;
-                if counter > BOUND && (counter.saturating_sub(BOUND) % CORRUPT_EVERY_N == 0) {
-                    // After BOUND, corrupt every CORRUPT_EVERY_Nth item.
-                    if let Some(last) = items.pop() {
-                        items.push(last.corrupt(corrupted));
-                    }
-                }
-            } else {
-                // Assert: not odd to even.
-                assert!((len % 2 == 0) || (items.len() % 2 == 1));
-            }
-        }
-        items.into_iter().next()
-    }
-
-    pub fn merge_children_multi_pass_binary(
-        items: Vec<Self>,
-        corrupted: &mut Vec<T>,
-    ) -> Option<Self> {
-        let mut digits: Vec<Option<Self>> = vec![];
-
-        // Add items one by one by simulating incrementing a binary number.
-        // We do this work in the merge_children of delete-min,
-        // but it's actually paid for by the inserts' O(1) charge already.
-        // So we don't need to corrupt here.
-        for item in items {
-            let mut carry = item;
-            // Make sure that we always have one trailing zero, ie trailing None,
-            // so that standard carry logic works.
-            if let Some(None) = digits.last() {
-            } else {
-                digits.push(None);
-            }
-            for digit in &mut digits {
-                match digit.take() {
-                    None => {
-                        *digit = Some(carry);
-                        break;
-                    }
-                    Some(digit_item) => {
-                        carry = carry.meld(digit_item);
-                    }
-                }
-            }
-        }
-        // Roll up our counter.
-        let mut digits = digits.into_iter().flatten();
-        let mut carry = digits.next()?;
-        let digits = enumerate(digits);
-        for (i, digit) in digits {
-            carry = carry.meld(digit);
-            if (i.saturating_sub(BOUND) + 1) % CORRUPT_EVERY_N == 0 {
-                // After BOUND, corrupt every CORRUPT_EVERY_Nth item.
-                carry = carry.corrupt(corrupted);
-            }
-        }
-        Some(carry)
-    }
-
-    // /// Merges the list of children of a (former) node into one node.
-    // ///
-    // /// See 'A Nearly-Tight Analysis of Multipass Pairing Heaps"
-    // /// by Corwin Sinnamon and Robert E. Tarjan.
-    // /// <https://epubs.siam.org/doi/epdf/10.1137/1.9781611977554.ch23>
-    // ///
-    // /// The paper explains why multipass (like here) does give O(log n)
-    // /// delete-min.
-    // ///
-    // /// (Originally O(log n) delete-min was only proven for the two-pass
-    // /// variant.)
-    // #[must_use]
-    // pub fn merge_children_pass_h_last_no_corruption(
-    //     mut items: Vec<Self>,
-    //     corrupted: &mut Vec<T>,
-    // ) -> Option<Self> {
-    //     // let start0 = previous_full_multiple(items.len(), CORRUPT_EVERY_N);
-    //     let start = items
-    //         .len()
-    //         .next_multiple_of(CORRUPT_EVERY_N)
-    //         .saturating_sub(CORRUPT_EVERY_N);
-    //     // assert!(start >= start0, "start: {start}, start0: {start0}, items.len(): {}", items.len());
-    //     assert_eq!(0, start % CORRUPT_EVERY_N);
-    //     assert!(items.len() - start < CORRUPT_EVERY_N);
-    //     // assert!(items.len() >= 0);
-    //     let last = Self::merge_many(items.drain(start..));
-    //     let binding = items.into_iter().chunks(CORRUPT_EVERY_N);
-    //     let chunked = binding
-    //         .into_iter()
-    //         .filter_map(Self::merge_many)
-    //         .map(|a| a.corrupt(corrupted));
-    //     Self::merge_many(chain!(chunked, once(last).flatten()))
-    // }
-
-    pub fn merge_children_pass_h(mut items: Vec<Self>, corrupted: &mut Vec<T>) -> Option<UnboundWitnessed<T>> {
-        let end = items.len() % CORRUPT_EVERY_N;
-        let start = items.len() - end;
-        let start0 = previous_full_multiple(items.len(), CORRUPT_EVERY_N);
-        // let start = items
-        //     .len()
-        //     .next_multiple_of(CORRUPT_EVERY_N)
-        //     .saturating_sub(CORRUPT_EVERY_N);
-        assert!(
-            start >= start0,
-            "start: {start}, start0: {start0}, items.len(): {}",
-            items.len()
-        );
-        assert_eq!(0, start % CORRUPT_EVERY_N);
-        assert!(items.len() - start < CORRUPT_EVERY_N);
-        // assert!(items.len() >= 0);
-        let last = Self::merge_many(items.drain(start..));
-        let binding = items.into_iter().chunks(CORRUPT_EVERY_N);
-        let chunked = binding
-            .into_iter()
-            .filter_map(Self::merge_many)
-            .map(|a| a.corrupt(corrupted));
-        Self::merge_many(chain!(chunked, once(last).flatten()))
-    }
-
-    pub fn merge_children_pass_h_queue(items: Vec<Self>, corrupted: &mut Vec<T>) -> Option<Self> {
-        let mut queue: VecDeque<_> = VecDeque::from(items);
-        let mut free: usize = 1;
-        loop {
-            if queue.len() <= free * CORRUPT_EVERY_N {
-                return Self::merge_many(queue);
-            }
-            if let Some(Pairing {
-                key: Pool { item, count },
-                mut children,
-            }) = Self::merge_many(queue.drain(..CORRUPT_EVERY_N))
-            {
-                free += 1;
-                corrupted.push(item);
-                // children.sort();
-                children.shuffle(&mut rand::rng());
-                // ch
-                // children.reverse();
-                if let Some(first) = children.first_mut() {
-                    first.key.count += count + 1;
-                } else {
-                    unreachable!(
-                        "We should always have at least one child after merging a full chunk."
-                    );
-                }
-                children.shuffle(&mut rand::rng());
-                queue.extend(children);
-            } else {
-                unreachable!("We should have have a non-empty heap after merging a full chunk.");
-            }
-        }
-    }
-
-    pub fn merge_children_pass_h_queue_power_of_n(
-        items: Vec<Self>,
-        corrupted: &mut Vec<T>,
-    ) -> Option<Self> {
-        let mut queue: VecDeque<_> = VecDeque::from(items);
-        loop {
-            if queue.len() < CORRUPT_EVERY_N {
-                return Self::merge_many(queue);
-            }
-            if let Some(Pairing {
-                key: Pool { item, count },
-                mut children,
-            }) = Self::merge_many(queue.drain(..CORRUPT_EVERY_N))
-            {
-                const POWER: usize = 10;
-
-                corrupted.push(item);
-                // children.sort();
-                children.shuffle(&mut rand::rng());
-
-                let mix = children.split_off(children.len().saturating_sub(POWER));
-                if let Some(r) = Self::merge_many(mix) {
-                    children.push(r);
-                } else {
-                    unreachable!("We should have a non-empty heap after merging a chunk.");
-                }
-
-                if let Some(last) = children.last_mut() {
-                    last.key.count += count + 1;
-                } else {
-                    unreachable!(
-                        "We should always have at least one child after merging a full chunk."
-                    );
-                }
-                children.shuffle(&mut rand::rng());
-                queue.extend(children);
-            } else {
-                unreachable!("We should have have a non-empty heap after merging a full chunk.");
-            }
-        }
-    }
-
-    pub fn merge_children_pass_h_queue_simple(
-        items: Vec<Self>,
-        corrupted: &mut Vec<T>,
+    /// Merges the children of a deleted node, and then corrupts only at the top.
+    ///
+    /// This is the 'corruption at the top' idea from `plan.markdown`: merging more than `bound`
+    /// children leaves us with `work = children - bound` to pay for.  We pay by corrupting about
+    /// `work / bound` of the smallest items in the merged heap, which we find with an inner soft
+    /// heap, like in 'Selection from heaps, row-sorted matrices and X+Y using soft heaps'.  Nodes
+    /// with more than [`TOP_MAX_CHILDREN`] children that we come across on the way get their
+    /// children merged first, and that work gets added to the bill.
+    ///
+    /// Newly corrupted items go into `corrupted`, one entry per item.  Each entry carries the
+    /// witnessed set of the corrupted node along as its children.
+    pub fn merge_children_top(
+        children: Vec<Self>,
+        bound: usize,
+        corrupted: &mut WitnessedSet<T>,
     ) -> Option<Self> {
-        let mut queue: VecDeque<Pairing<T>> = VecDeque::from(items);
-        loop {
-            if queue.len() <= CORRUPT_EVERY_N {
-                return Self::merge_many(queue);
-            }
-            if let Some(p) = Self::merge_many(queue.drain(..CORRUPT_EVERY_N)) {
-                queue.push_back(p.corrupt(corrupted));
-            } else {
-                unreachable!("We should have have a non-empty heap after merging a full chunk.");
-            }
-        }
+        Self::merge_top(children, bound, &mut Selected::Pool(corrupted))
     }
 
+    /// Like [`Self::merge_children_top`], but doesn't corrupt anything yet.
+    ///
+    /// The items that `merge_children_top` would pool go into the witnessed set of the new root
+    /// instead, and keep their own keys for now.  See [`LateHeap`] for when they turn into real
+    /// corruption.
     #[must_use]
-    // multi-pass, evenly distributed corruption
-    pub fn merge_children_evenly(items: Vec<Self>, corrupted: &mut Vec<T>) -> Option<Self> {
-        // let mut d: VecDeque<_> = items
-        //     .into_iter()
-        //     .chunks(2)
-        //     .into_iter()
-        //     .filter_map(|chunk| chunk.reduce(Self::meld))
-        //     .collect();
-        let mut d: VecDeque<_> = VecDeque::from(items);
-        for c in 1.. {
-            let next = match (d.pop_front(), d.pop_front()) {
-                (Some(a), Some(b)) => a.meld(b),
-                (a, _) => return a,
-            };
-            d.push_back(if c % CORRUPT_EVERY_N == 0 {
-                next.corrupt(corrupted)
-            } else {
-                next
-            });
-        }
-        unreachable!()
-    }
-
-    #[must_use]
-    pub fn merge_children_two_pass(items: Vec<Self>, corrupted: &mut Vec<T>) -> Option<Self> {
-        items
-            .into_iter()
-            .chunks(2)
-            .into_iter()
-            .filter_map(|chunk| chunk.reduce(Self::meld))
-            .chunks(CORRUPT_EVERY_N)
-            .into_iter()
-            .fold(None, |acc, item| {
-                chain!(acc.map(|acc| acc.corrupt(corrupted)), item).reduce(Self::meld)
-            })
+    pub fn merge_children_late(children: Vec<Self>, bound: usize) -> Option<Self> {
+        Self::merge_top(children, bound, &mut Selected::Hide)
     }
 
-    #[must_use]
-    pub fn merge_children_two_pass_grouped(
-        items: Vec<Self>,
-        corrupted: &mut Vec<T>,
+    fn merge_top(
+        children: Vec<Self>,
+        bound: usize,
+        selected_to: &mut Selected<'_, T>,
     ) -> Option<Self> {
-        let mut queue: VecDeque<_> = VecDeque::from(items);
-        loop {
-            if queue.len() < CORRUPT_EVERY_N {
-                return Self::merge_many(queue);
-            }
-            if let Some(p) = Self::merge_many(queue.drain(..CORRUPT_EVERY_N)) {
-                queue.push_front(p.corrupt(corrupted));
-            } else {
-                unreachable!("We should have have a non-empty heap after merging a full chunk.");
-            }
+        let mut work = children.len().saturating_sub(bound);
+        let merged = Self::merge_many(children)?;
+        if work < bound {
+            return Some(merged);
         }
-    }
 
-    #[must_use]
-    pub fn merge_children_two_pass_grouped_last(
-        items: Vec<Self>,
-        corrupted: &mut Vec<T>,
-    ) -> Option<Self> {
-        let mut queue: VecDeque<_> = VecDeque::from(items);
-        loop {
-            if queue.len() < CORRUPT_EVERY_N {
-                return Self::merge_many(queue);
-            }
-            if let Some(p) = Self::merge_many(queue.drain(..CORRUPT_EVERY_N)) {
-                queue.push_back(p.corrupt(corrupted));
-            } else {
-                unreachable!("We should have have a non-empty heap after merging a full chunk.");
+        let mut inner = SoftHeap::with_epsilon(TOP_INNER_EPSILON);
+        inner.push(ByRoot(merged));
+        let mut selected = vec![];
+        while inner.inserted - inner.size < work / bound {
+            let (node, witnessed) = inner.heavy_pop();
+            let Some(node) = node else {
+                break;
+            };
+            for ByRoot(mut node) in std::iter::once(node).chain(witnessed) {
+                let mut children = mem::take(&mut node.children).into_vec();
+                if children.len() > TOP_MAX_CHILDREN {
+                    work += children.len().saturating_sub(bound);
+                    children = Self::merge_top(children, bound, selected_to)
+                        .into_iter()
+                        .collect();
+                }
+                inner.extend(children.into_iter().map(ByRoot));
+                selected.push(node);
             }
         }
-    }
 
-    #[must_use]
-    pub fn merge_children_multi_grouped(items: Vec<Self>, corrupted: &mut Vec<T>) -> Option<Self> {
-        const EVERY_SECOND_LAYER: usize = 4;
-        let binding = items.into_iter().chunks(CORRUPT_EVERY_N);
-        let mut queue: VecDeque<_> = binding.into_iter().filter_map(Self::merge_many).collect();
-        loop {
-            if queue.len() < EVERY_SECOND_LAYER {
-                return Self::merge_many(queue);
-            }
-            let res = Self::merge_many(queue.drain(..EVERY_SECOND_LAYER))
-                .expect("We should have have a non-empty heap after merging a full chunk.")
-                .corrupt(corrupted);
-            queue.push_back(res);
-            // if let Some(p) = Self::merge_many(queue.drain(..EVERY_SECOND_LAYER)) {
-            //     // queue.push_back(p.corrupt(corrupted));
-            //     queue.push_back(p.corrupt(corrupted));
-            // } else {
-            //     unreachable!("We should have have a non-empty heap after merging a full chunk.");
-            // }
-        }
+        // Whatever the inner heap didn't give up goes back together.  The inner heap's own
+        // corruption doesn't matter: we only use it to decide what to select.
+        let rest = Self::merge_many(inner.into_all_items().into_iter().map(|ByRoot(node)| node));
+        Some(Self::pool_into(rest, selected, selected_to))
     }
 
-    #[must_use]
-    pub fn merge_children_multi_grouped_less_grace(
-        items: Vec<Self>,
-        corrupted: &mut Vec<T>,
-    ) -> Option<Self> {
-        const EVERY_SECOND_LAYER: usize = 4;
-        let binding = items.into_iter().chunks(CORRUPT_EVERY_N);
-        let mut queue: VecDeque<_> = binding.into_iter().filter_map(Self::merge_many).collect();
-        loop {
-            if queue.len() < EVERY_SECOND_LAYER {
-                return Self::merge_many(queue);
-            }
-
-            if let Some(p) = Self::merge_many(
-                queue
-                    .drain(..EVERY_SECOND_LAYER.min(queue.len()))
-                    .map(|p| p.corrupt(corrupted)),
-            ) {
-                // queue.push_back(p.corrupt(corrupted));
-                queue.push_front(p);
+    /// Pools the `selected` nodes, which have no children left, into the root of `rest`.  Or
+    /// hides them in its witnessed set, see [`Selected`].
+    ///
+    /// Nodes that are bigger than that root can't be pooled into it, so they go back in as they
+    /// are.
+    fn pool_into(
+        rest: Option<Self>,
+        mut selected: Vec<Self>,
+        selected_to: &mut Selected<'_, T>,
+    ) -> Self {
+        let mut root = rest.unwrap_or_else(|| {
+            let biggest = (0..selected.len())
+                .reduce(|a, b| {
+                    if selected[a].key.item < selected[b].key.item {
+                        b
+                    } else {
+                        a
+                    }
+                })
+                .expect("We only select from a non-empty heap.");
+            selected.swap_remove(biggest)
+        });
+        for node in selected {
+            if node.key.item <= root.key.item {
+                let Pairing { key, witnessed, .. } = node;
+                // The node's own pool is corrupted already, so it moves over either way.
+                root.key.count += key.count;
+                let entry = Witnessed {
+                    item: key.item,
+                    count: 1 + witnessed.count,
+                    children: witnessed,
+                };
+                match selected_to {
+                    Selected::Pool(corrupted) => {
+                        root.key.count += 1;
+                        corrupted.add_child(entry);
+                    }
+                    Selected::Hide => root.witnessed.add_child(entry),
+                }
             } else {
-                unreachable!("We should have have a non-empty heap after merging a full chunk.");
+                root = root.meld(node);
             }
         }
+        root
     }
+}
 
-    #[must_use]
-    // This one does not work!  Leads to 100% corruption.
-    // Probably for the same reason it leads to amortised O(n) delete-min
-    pub fn merge_children_one_pass(items: Vec<Self>, corrupted: &mut Vec<T>) -> Option<Self> {
-        items
-            .into_iter()
-            // .chunks(2)
-            // .into_iter()
-            // .filter_map(|chunk| chunk.reduce(Self::meld))
-            .chunks(CORRUPT_EVERY_N)
-            .into_iter()
-            .fold(None, |acc, item| {
-                chain!(acc.map(|acc| acc.corrupt(corrupted)), item).reduce(Self::meld)
-            })
-    }
-
-    #[must_use]
-    // This one does not work!  Leads to 100% corruption.
-    pub fn merge_children_two_pass_degree(
-        items: Vec<Self>,
-        corrupted: &mut Vec<T>,
-    ) -> Option<Self> {
-        items
-            .into_iter()
-            .chunks(2)
-            .into_iter()
-            .filter_map(|chunk| chunk.reduce(Self::meld))
-            .reduce(|a, b| {
-                let acc = a.meld(b);
-                if acc.children.len() > CORRUPT_EVERY_N {
-                    acc.corrupt(corrupted)
-                } else {
-                    acc
-                }
-            })
-    }
-
-    pub fn merge_children(items: Vec<Self>, corrupted: &mut Vec<T>) -> Option<UnboundWitnessed<T>> {
-        // These two work, really well:
-        // Self::merge_children_multi_pass_binary(items, corrupted)
-        // Self::merge_children_multi_pass_binary_implicit(items, corrupted)
-
-        // This one doesn't actually do any corruption
-        // Self::merge_children_bounded(items, corrupted)
-
-        // This one seems to work, but it has a higher corruption rate for our parameter.
-        // Self::merge_children_evenly(items, corrupted)
+/// What [`Pairing::merge_children_top`] does with the items it selects for corruption.
+enum Selected<'a, T> {
+    /// Pool them into the root right away, and report them here.
+    Pool(&'a mut WitnessedSet<T>),
+    /// Hide them in the witnessed set of the root, without corrupting them yet.
+    Hide,
+}
 
-        // These ones work:
-        Self::merge_children_pass_h(items,)
-        // Self::merge_children_pass_h_queue_simple(items, corrupted)
-        // Self::merge_children_two_pass(items, corrupted)
-        // Self::merge_children_two_pass_grouped(items, corrupted)
-        // Self::merge_children_two_pass_grouped_last(items, corrupted)
+/// How many children a node may have, before [`Pairing::merge_children_top`] merges them.
+const TOP_MAX_CHILDREN: usize = 2;
 
-        // Self::merge_children_multi_grouped(items, corrupted)
-        // Self::merge_children_multi_grouped_less_grace(items, corrupted)
+/// Error rate of the inner soft heap in [`Pairing::merge_children_top`].
+const TOP_INNER_EPSILON: f64 = 0.25;
 
-        // These ones should maybe work, but doesn't:
-        // Self::merge_children_pass_h_queue(items, corrupted)
-        // Self::merge_children_pass_h_queue_power_of_n(items, corrupted)
+/// Orders subtrees by their root's key alone.
+struct ByRoot<T>(Pairing<T>);
 
-        // // These two do not work!
-        // Self::merge_children_two_pass_degree(items, corrupted)
-        // Self::merge_children_one_pass(items, corrupted)
-        // Self::merge_children_at_end(items, corrupted)
+impl<T: Ord> Ord for ByRoot<T> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.key.item.cmp(&other.0.key.item)
     }
-
-    // Corrupt all at the end.
-    #[must_use]
-    pub fn merge_children_at_end(items: Vec<Self>, corrupted: &mut Vec<T>) -> Option<Self> {
-        let l = items.len().max(1);
-        let mut d: VecDeque<_> = VecDeque::from(items);
-        // Total = l-1 comparisons.
-        // So we need floor(l / EVERY) corruptions at the end.
-        let end = l - l / CORRUPT_EVERY_N;
-        assert!(end <= l, "end: {end}, l: {l}");
-        assert!((l - end) * CORRUPT_EVERY_N <= l);
-        for c in 1.. {
-            assert!(c <= l, "c: {c}, l: {l}");
-            let next = match (d.pop_front(), d.pop_front()) {
-                (Some(a), Some(b)) => a.meld(b),
-                (a, _) => {
-                    assert_eq!(c, l);
-                    return a;
-                }
-            };
-            d.push_back(if c > end {
-                next.corrupt(corrupted)
-            } else {
-                next
-            });
-        }
-        unreachable!()
+}
+impl<T: Ord> PartialOrd for ByRoot<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
     }
-
-    pub fn check_heap_property(&self) -> bool {
-        let Pairing { key, children } = self;
-        children.iter().all(|child| key.item <= child.key.item)
-            && children.iter().all(Self::check_heap_property)
+}
+impl<T: Ord> PartialEq for ByRoot<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
     }
-    */
 }
+impl<T: Ord> Eq for ByRoot<T> {}
 
 // Get all non-corrupted elements still in the heap.
 impl<T> From<Pairing<T>> for Vec<T> {
@@ -897,46 +783,178 @@ impl<T> From<Pairing<T>> for Vec<T> {
 // Also: actually use the soft pairing heap for my Schubert matroid.
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
-pub struct SoftHeap<T> {
+pub struct SoftHeap<T, M = PassH> {
     pub root: Option<Pairing<T>>,
     pub size: usize,
     pub corrupted: usize,
     pub corrupt_every_n: usize,
+    /// How to merge the children of a deleted root.
+    pub strategy: M,
+    /// Total number of items ever inserted, including melded heaps.
+    pub inserted: usize,
+    /// Whether [`SoftHeap::try_pop_min`] and friends check the corruption budget, see
+    /// [`SoftHeap::guarded`].
+    pub guarded: bool,
+}
+
+/// A soft heap had more corrupted items than its [`estimated_epsilon`] allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetExceeded {
+    /// Corrupted items in the heap right after the offending pop.
+    pub corrupted: usize,
+    /// Items inserted into the heap so far.
+    pub inserted: usize,
+    pub corrupt_every_n: usize,
+}
+
+impl Display for BudgetExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} of {} inserted items are corrupted, more than epsilon = {} allows for corrupt_every_n = {}",
+            self.corrupted,
+            self.inserted,
+            estimated_epsilon(self.corrupt_every_n),
+            self.corrupt_every_n
+        )
+    }
+}
+
+impl Error for BudgetExceeded {}
+
+/// Our estimate of the worst-case fraction of inserted items that a soft heap with
+/// `corrupt_every_n` can have corrupted at any one time, ie of `corrupted / inserted`.
+///
+/// This is an estimate, not a proven bound.  Each corruption costs a full chunk of
+/// `corrupt_every_n` merged children, but a few of those melds don't pay for themselves.  Giving
+/// up three of them has been enough in all of our tests and experiments, but we don't have a
+/// proof.
+///
+/// Below five, we don't estimate anything.  With `corrupt_every_n = 4`, ascending inserts
+/// followed by popping everything stay under a third, but shuffled inserts with a pop after
+/// every tenth insert keep more than half of all items corrupted.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub const fn estimated_epsilon(corrupt_every_n: usize) -> f64 {
+    if corrupt_every_n <= 4 {
+        1.0
+    } else {
+        1.0 / (corrupt_every_n - 3) as f64
+    }
+}
+
+/// The smallest `corrupt_every_n` with a [`estimated_epsilon`] of at most `epsilon`.
+///
+/// Tiny values of `epsilon` saturate at `usize::MAX`, ie a soft heap that never corrupts.
+///
+/// # Panics
+///
+/// Panics unless `0 < epsilon <= 1`.  That includes NaN.
+#[must_use]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub const fn corrupt_every_n_for(epsilon: f64) -> usize {
+    assert!(
+        0.0 < epsilon && epsilon <= 1.0,
+        "epsilon has to be in (0, 1]"
+    );
+    // Float to int casts saturate, so only the addition could overflow.
+    ((1.0 / epsilon).ceil() as usize).saturating_add(3)
 }
 
+// `new` and `singleton` only exist for the default strategy, so that type inference doesn't
+// need any hints.  Use `with_strategy` for the others.
 impl<T> SoftHeap<T> {
     #[must_use]
     pub fn singleton(corrupt_every_n: usize, item: T) -> Self {
-        Self {
-            root: Some(Pairing::new(item)),
-            size: 1,
-            corrupted: 0,
-            corrupt_every_n,
-        }
+        Self::with_strategy(corrupt_every_n, PassH).insert_root(item)
     }
     #[must_use]
     pub fn new(corrupt_every_n: usize) -> Self {
-        Self {
+        Self::with_strategy(corrupt_every_n, PassH)
+    }
+    /// Makes a soft heap that should never have more than `epsilon` of its inserted items
+    /// corrupted.  That's an estimate, not a guarantee, see [`estimated_epsilon`].
+    ///
+    /// See [`corrupt_every_n_for`].
+    #[must_use]
+    pub fn with_epsilon(epsilon: f64) -> Self {
+        Self::new(corrupt_every_n_for(epsilon))
+    }
+}
+
+impl<T, M: MergeStrategy> SoftHeap<T, M> {
+    /// # Panics
+    ///
+    /// Panics if `corrupt_every_n < 2`.  With chunks of a single heap, strategies would corrupt
+    /// lone leaves, which have nothing to pool into.
+    #[must_use]
+    pub fn with_strategy(corrupt_every_n: usize, strategy: M) -> Self {
+        assert!(corrupt_every_n >= 2, "corrupt_every_n has to be at least 2");
+        Self {
             root: None,
             size: 0,
             corrupted: 0,
             corrupt_every_n,
+            strategy,
+            inserted: 0,
+            guarded: false,
+        }
+    }
+
+    /// Turns on the corruption budget check in [`SoftHeap::try_pop_min`],
+    /// [`SoftHeap::try_heavy_pop_min`], [`SoftHeap::try_pop`] and [`SoftHeap::try_heavy_pop`].
+    ///
+    /// The check is cheap, but only fails if our [`estimated_epsilon`] is too low.  So it's for
+    /// callers who'd rather bail out than carry on with a heap that's more corrupted than they
+    /// planned for.
+    #[must_use]
+    pub fn guarded(self) -> Self {
+        Self {
+            guarded: true,
+            ..self
+        }
+    }
+
+    /// Checks `corrupted <= epsilon * inserted`, if the heap is [`SoftHeap::guarded`].
+    #[allow(clippy::cast_precision_loss)]
+    pub fn check_budget(&self) -> Result<(), BudgetExceeded> {
+        if self.guarded && self.corrupted as f64 > self.epsilon() * self.inserted as f64 {
+            Err(BudgetExceeded {
+                corrupted: self.corrupted,
+                inserted: self.inserted,
+                corrupt_every_n: self.corrupt_every_n,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Estimated upper bound on `corrupted / inserted`, see [`estimated_epsilon`].
+    #[must_use]
+    pub const fn epsilon(&self) -> f64 {
+        estimated_epsilon(self.corrupt_every_n)
+    }
+
+    fn insert_root(self, item: T) -> Self {
+        debug_assert!(self.root.is_none());
+        Self {
+            root: Some(Pairing::new(item)),
+            size: 1,
+            inserted: self.inserted + 1,
+            ..self
         }
     }
 }
 
-impl<T: Ord> SoftHeap<T> {
+impl<T: Ord, M: MergeStrategy> SoftHeap<T, M> {
     #[must_use]
     pub fn insert(self, item: T) -> Self {
         match self.root {
-            None => Self {
-                root: Some(Pairing::new(item)),
-                size: 1,
-                ..self
-            },
+            None => self.insert_root(item),
             Some(root) => Self {
                 root: Some(root.insert(item)),
                 size: self.size + 1,
+                inserted: self.inserted + 1,
                 ..self
             },
         }
@@ -949,7 +967,8 @@ impl<T: Ord> SoftHeap<T> {
             root,
             size: self.size + other.size,
             corrupted: self.corrupted + other.corrupted,
-            corrupt_every_n: self.corrupt_every_n,
+            inserted: self.inserted + other.inserted,
+            ..self
         }
     }
 
@@ -958,14 +977,14 @@ impl<T: Ord> SoftHeap<T> {
         match self.root {
             None => (self, None, vec![]),
             Some(root) => {
-                let (root, pool, corrupted) =
-                    UnboundWitnessed::from(root).heavy_pop_min(self.corrupt_every_n);
+                let (root, pool, corrupted) = UnboundWitnessed::<T>::from(root)
+                    .heavy_pop_min(self.corrupt_every_n, self.strategy);
                 (
                     Self {
                         root,
                         size: self.size - pool.count - 1,
                         corrupted: self.corrupted + corrupted.count - pool.count,
-                        corrupt_every_n: self.corrupt_every_n,
+                        ..self
                     },
                     Some(pool.item),
                     Vec::from(corrupted),
@@ -981,29 +1000,55 @@ impl<T: Ord> SoftHeap<T> {
         (me, corrupted)
     }
 
+    /// Like [`SoftHeap::pop_min`], but fails if the pop leaves a [`SoftHeap::guarded`] heap with
+    /// more corruption than it's allowed.
+    pub fn try_pop_min(self) -> Result<(Self, Option<T>, Vec<T>), BudgetExceeded> {
+        Self::within_budget(self.pop_min())
+    }
+
+    /// Like [`SoftHeap::heavy_pop_min`], but checks the budget like [`SoftHeap::try_pop_min`].
+    pub fn try_heavy_pop_min(self) -> Result<(Self, Option<T>, Vec<T>), BudgetExceeded> {
+        Self::within_budget(self.heavy_pop_min())
+    }
+
+    /// Where all the fallible pops check the budget, after popping.
+    fn within_budget(
+        popped: (Self, Option<T>, Vec<T>),
+    ) -> Result<(Self, Option<T>, Vec<T>), BudgetExceeded> {
+        popped.0.check_budget()?;
+        Ok(popped)
+    }
+
     #[must_use]
     pub fn pop_min(self) -> (Self, Option<T>, Vec<T>) {
+        let (me, item, corrupted) = self.pop_min_reported::<WitnessedSet<T>>();
+        (me, item, Vec::from(corrupted))
+    }
+
+    /// The guts of [`SoftHeap::pop_min`].  `R` decides when and how corrupted items get
+    /// reported, see [`Report`].
+    fn pop_min_reported<R: Report<T>>(self) -> (Self, Option<T>, R) {
         // TODO: simplify.
         match self.root {
-            None => (self, None, vec![]),
+            None => (self, None, R::default()),
             Some(root) => {
-                let (root, item, corrupted) =
-                    UnboundWitnessed::from(root).pop_min(self.corrupt_every_n);
+                let (root, item, corrupted) = UnboundWitnessed::<T, R>::from(root)
+                    .pop_min(self.corrupt_every_n, self.strategy);
                 (
                     Self {
                         root,
                         size: self.size - 1,
-                        corrupted: self.corrupted + corrupted.count - usize::from(item.is_none()),
-                        corrupt_every_n: self.corrupt_every_n,
+                        corrupted: self.corrupted + corrupted.count() - usize::from(item.is_none()),
+                        ..self
                     },
                     item,
-                    Vec::from(corrupted),
+                    corrupted,
                 )
             }
         }
     }
 }
-impl<T> SoftHeap<T> {
+impl<T, M: MergeStrategy> SoftHeap<T, M> {
     pub fn count_delayed_corruption(&self) -> usize {
         self.root
             .as_ref()
@@ -1032,56 +1077,432 @@ impl<T> SoftHeap<T> {
         debug_assert_eq!(self.size > 0, self.root.is_some());
         self.root.is_none()
     }
+
+    /// The item at the root, ie the smallest apparent key.  Doesn't change the heap.
+    ///
+    /// If the root is a pool, this is the item the pooled corrupted items pretend to be.
+    pub fn peek(&self) -> Option<&T> {
+        self.root.as_ref().map(|root| &root.key.item)
+    }
+
+    /// The pool at the root, without popping anything, so without corrupting anything either.
+    ///
+    /// `count` is the number of corrupted items pooled under the root's item.  They all have the
+    /// root's item as their apparent key.
+    pub fn peek_min(&self) -> Option<&Pool<T>> {
+        self.root.as_ref().map(|root| &root.key)
+    }
+
+    /// Whether the next [`SoftHeap::pop_min`] returns a real item.
+    ///
+    /// That's the case, when the root's pool is already drained.  Otherwise, `pop_min` only
+    /// removes one of the pooled corrupted items, and returns no item.  (A
+    /// [`SoftHeap::heavy_pop_min`] always returns the root's item.)
+    pub fn next_pop_is_item(&self) -> bool {
+        self.peek_min().is_some_and(|pool| pool.count == 0)
+    }
+
+    /// All items still in the heap, corrupted or not, in no particular order.
+    ///
+    /// Doesn't compare anything.  Corrupted items are waiting in the witness sets of the nodes.
+    pub fn into_all_items(self) -> Vec<T> {
+        let mut items = vec![];
+        let mut todo: Vec<Pairing<T>> = self.root.into_iter().collect();
+        while let Some(Pairing {
+            key,
+            witnessed,
+            children,
+        }) = todo.pop()
+        {
+            items.push(key.item);
+            witnessed.in_order(&mut items);
+            todo.extend(children);
+        }
+        debug_assert_eq!(items.len(), self.size);
+        items
+    }
+
+    /// Moves the heap out of `self`, and leaves an empty heap with the same parameter behind.
+    fn take(&mut self) -> Self {
+        let empty = Self {
+            guarded: self.guarded,
+            ..Self::with_strategy(self.corrupt_every_n, self.strategy)
+        };
+        mem::replace(self, empty)
+    }
+}
+
+// In-place versions of the operations above, like for `std::collections::BinaryHeap`.
+impl<T: Ord, M: MergeStrategy> SoftHeap<T, M> {
+    pub fn push(&mut self, item: T) {
+        *self = self.take().insert(item);
+    }
+
+    /// Like [`SoftHeap::pop_min`]: returns the popped item (if it wasn't corrupted), and the
+    /// items that got corrupted along the way.
+    pub fn pop(&mut self) -> (Option<T>, Vec<T>) {
+        let (me, item, corrupted) = self.take().pop_min();
+        *self = me;
+        (item, corrupted)
+    }
+
+    /// Like [`SoftHeap::heavy_pop_min`].
+    pub fn heavy_pop(&mut self) -> (Option<T>, Vec<T>) {
+        let (me, item, corrupted) = self.take().heavy_pop_min();
+        *self = me;
+        (item, corrupted)
+    }
+
+    /// Like [`SoftHeap::try_pop_min`].  On error the pop still happened, and the heap stays
+    /// usable, but the popped items are gone.
+    pub fn try_pop(&mut self) -> Result<(Option<T>, Vec<T>), BudgetExceeded> {
+        let popped = self.pop();
+        self.check_budget()?;
+        Ok(popped)
+    }
+
+    /// Like [`SoftHeap::try_heavy_pop_min`], and [`SoftHeap::try_pop`].
+    pub fn try_heavy_pop(&mut self) -> Result<(Option<T>, Vec<T>), BudgetExceeded> {
+        let popped = self.heavy_pop();
+        self.check_budget()?;
+        Ok(popped)
+    }
+
+    /// Moves all items of `other` into `self`, leaving `other` empty.
+    pub fn append(&mut self, other: &mut Self) {
+        *self = self.take().meld(other.take());
+    }
+}
+
+/// What one [`SoftHeap::pop_min_events`] did.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub enum PopEvent<T> {
+    /// The root's item left the heap.  It was never corrupted.
+    Popped(T),
+    /// One anonymous corrupted item left the pool at the root.  All items in that pool have a
+    /// true key of at most `key`, and `remaining` of them are still in the heap.
+    PoolDrained { key: T, remaining: usize },
+    /// `item` just got pooled under `apparent_key`, so its true key is at most that.
+    Corrupted { item: T, apparent_key: T },
+    /// A corrupted item that an earlier [`SoftHeap::pop_min`] or [`SoftHeap::heavy_pop_min`]
+    /// left waiting in a witnessed set.  We don't know which pool it's in.
+    Witnessed(T),
 }
 
-impl<T> From<SoftHeap<T>> for Vec<T> {
-    fn from(SoftHeap { root, .. }: SoftHeap<T>) -> Self {
+/// Reports corrupted items right away, each with the key it got pooled under.  Only for
+/// [`SoftHeap::pop_min_events`].
+struct Named<T> {
+    /// What plain pops left waiting in witnessed sets.  We treat it like they would.
+    witnessed: WitnessedSet<T>,
+    /// Corrupted items, each with a copy of the key of its pool.
+    pooled: Vec<(T, T)>,
+}
+
+impl<T> Default for Named<T> {
+    fn default() -> Self {
+        Self {
+            witnessed: WitnessedSet::default(),
+            pooled: vec![],
+        }
+    }
+}
+
+impl<T: Clone> Report<T> for Named<T> {
+    fn count(&self) -> usize {
+        self.witnessed.count + self.pooled.len()
+    }
+    fn append(&mut self, mut other: Self) {
+        self.witnessed.extend(mem::take(&mut other.witnessed));
+        self.pooled.append(&mut other.pooled);
+    }
+    fn witness(&mut self, witnessed: WitnessedSet<T>) {
+        self.witnessed.extend(witnessed);
+    }
+    fn pool(&mut self, item: T, pool: &T) {
+        self.pooled.push((item, pool.clone()));
+    }
+    fn hide(mut self, witnessed: &mut WitnessedSet<T>) -> Self {
+        witnessed.extend(mem::take(&mut self.witnessed));
+        self
+    }
+}
+
+impl<T: Ord + Clone, M: MergeStrategy> SoftHeap<T, M> {
+    /// Like [`SoftHeap::pop_min`], but tells us for every corrupted item what upper bound we
+    /// know for its key.
+    ///
+    /// Reports corrupted items as soon as they get pooled, as [`PopEvent::Corrupted`] with the
+    /// key of their pool.  That's often earlier than `pop_min` would report them: `pop_min`
+    /// leaves some of them waiting in the witnessed set of another node, until that node gets
+    /// deleted, and by then we no longer know which pool they are in.
+    ///
+    /// Each item comes out exactly once, as [`PopEvent::Popped`], [`PopEvent::Corrupted`] or
+    /// [`PopEvent::Witnessed`].  The last one only happens if we mix in plain pops, for the
+    /// items that they left waiting.  Pool slots leave the heap later, anonymously, via
+    /// [`PopEvent::PoolDrained`].
+    #[must_use]
+    pub fn pop_min_events(self) -> (Self, Vec<PopEvent<T>>) {
+        let Some(pool) = self.peek_min().cloned() else {
+            return (self, vec![]);
+        };
+        let (me, item, Named { witnessed, pooled }) = self.pop_min_reported();
+        let mut events: Vec<_> = pooled
+            .into_iter()
+            .map(|(item, apparent_key)| PopEvent::Corrupted { item, apparent_key })
+            .chain(Vec::from(witnessed).into_iter().map(PopEvent::Witnessed))
+            .collect();
+        events.push(match item {
+            None => PopEvent::PoolDrained {
+                key: pool.item,
+                remaining: pool.count - 1,
+            },
+            Some(item) => PopEvent::Popped(item),
+        });
+        (me, events)
+    }
+
+    /// Like [`SoftHeap::pop_min_events`].
+    pub fn pop_events(&mut self) -> Vec<PopEvent<T>> {
+        let (me, events) = self.take().pop_min_events();
+        *self = me;
+        events
+    }
+}
+
+impl<T, M> From<SoftHeap<T, M>> for Vec<T> {
+    fn from(SoftHeap { root, .. }: SoftHeap<T, M>) -> Self {
         root.map(Vec::from).unwrap_or_default()
     }
 }
 
-impl<T: Ord> Extend<T> for SoftHeap<T> {
+impl<T: Ord, M: MergeStrategy> Extend<T> for SoftHeap<T, M> {
     fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
-        // TODO(Matthias): do we really need to replace self?  Can we do this with some borrowing?
-        let mut me = mem::replace(self, Self::new(self.corrupt_every_n));
         for item in iter {
-            me = me.insert(item);
+            self.push(item);
         }
-        *self = me;
     }
 }
+/// A soft heap that only ever corrupts the smallest items, see [`Pairing::merge_children_top`].
+///
+/// Corrupted items get reported as soon as they are corrupted, like the classic soft heap.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct TopHeap<T> {
+    pub root: Option<Pairing<T>>,
+    pub size: usize,
+    pub corrupted: usize,
+    /// How many children we merge for free, `B` in `plan.markdown`.  Corrupts about one item
+    /// per `bound` children merged beyond that.
+    ///
+    /// Any `bound` of at least one works, but corruption only stays in check while `bound` is
+    /// above a threshold that grows with the heap, roughly like `log2(n)`.  Below that, the
+    /// recursive merges pay for more corruption than they save, and corruption runs away: with
+    /// 100k items and a bound of 8, about two thirds of all items end up corrupted, and even a
+    /// bound of 16 runs away at about 300k items.  See the sweeps in `plan.markdown`.
+    pub bound: usize,
+}
+
+impl<T> TopHeap<T> {
+    /// # Panics
+    ///
+    /// Panics if `bound` is zero.
+    #[must_use]
+    pub fn new(bound: usize) -> Self {
+        assert!(bound > 0, "bound has to be positive");
+        Self {
+            root: None,
+            size: 0,
+            corrupted: 0,
+            bound,
+        }
+    }
+
+    pub fn count_corrupted(&self) -> usize {
+        self.corrupted
+    }
+    pub fn count_uncorrupted(&self) -> usize {
+        self.size - self.corrupted
+    }
+    pub fn is_empty(&self) -> bool {
+        debug_assert_eq!(self.size > 0, self.root.is_some());
+        self.root.is_none()
+    }
+}
+
+impl<T: Ord> TopHeap<T> {
+    #[must_use]
+    pub fn insert(self, item: T) -> Self {
+        let root = Pairing::meld_option(self.root, Some(Pairing::new(item)));
+        Self {
+            root,
+            size: self.size + 1,
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn meld(self, other: Self) -> Self {
+        let root = Pairing::meld_option(self.root, other.root);
+        Self {
+            root,
+            size: self.size + other.size,
+            corrupted: self.corrupted + other.corrupted,
+            ..self
+        }
+    }
+
+    /// Removes one item with the smallest apparent key.
+    ///
+    /// Returns the item, unless it's one of the corrupted items pooled at the root, and all the
+    /// items that got corrupted along the way.
+    #[must_use]
+    pub fn pop_min(self) -> (Self, Option<T>, Vec<T>) {
+        let Some(Pairing {
+            key,
+            witnessed,
+            children,
+        }) = self.root
+        else {
+            return (self, None, vec![]);
+        };
+        match key.delete_one() {
+            Ok(key) => (
+                Self {
+                    root: Some(Pairing {
+                        key,
+                        witnessed,
+                        children,
+                    }),
+                    size: self.size - 1,
+                    corrupted: self.corrupted - 1,
+                    ..self
+                },
+                None,
+                vec![],
+            ),
+            Err(item) => {
+                let mut corrupted = WitnessedSet::default();
+                let root =
+                    Pairing::merge_children_top(children.into_vec(), self.bound, &mut corrupted);
+                let corrupted = Vec::from(corrupted);
+                (
+                    Self {
+                        root,
+                        size: self.size - 1,
+                        corrupted: self.corrupted + corrupted.len(),
+                        ..self
+                    },
+                    Some(item),
+                    corrupted,
+                )
+            }
+        }
+    }
+
+    /// Removes the root item together with all the corrupted items pooled with it.
+    #[must_use]
+    pub fn heavy_pop_min(self) -> (Self, Option<T>, Vec<T>) {
+        let Some(Pairing { key, children, .. }) = self.root else {
+            return (self, None, vec![]);
+        };
+        let mut corrupted = WitnessedSet::default();
+        let root = Pairing::merge_children_top(children.into_vec(), self.bound, &mut corrupted);
+        let corrupted = Vec::from(corrupted);
+        (
+            Self {
+                root,
+                size: self.size - key.count - 1,
+                corrupted: self.corrupted + corrupted.len() - key.count,
+                ..self
+            },
+            Some(key.item),
+            corrupted,
+        )
+    }
+}
+
+impl<T> From<TopHeap<T>> for Vec<T> {
+    fn from(TopHeap { root, .. }: TopHeap<T>) -> Self {
+        root.map(Vec::from).unwrap_or_default()
+    }
+}
+
+/// A soft heap that selects the same items for corruption as [`TopHeap`], but only corrupts
+/// them once they are witnessed.
+///
+/// Instead of pooling the selected items into the new root, we record them in the root's
+/// witnessed set, see [`Pairing::merge_children_late`].  There they wait, with their own keys and
+/// without counting towards [`LateHeap::count_corrupted`], until a pop finds them at the root.
+/// Only then do they turn into real corruption: they get pooled under the root's key, just like
+/// [`TopHeap`] would have done right away, and reported.  Every item gets reported exactly once:
+/// either popped, or when it's witnessed.
+///
+/// Until then, [`LateHeap::count_delayed_corruption`] counts them.  Both heaps have the same
+/// shape, so the real and the delayed corruption of a `LateHeap` always add up to the corruption
+/// of a [`TopHeap`] that went through the same operations.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct LateHeap<T> {
     pub root: Option<Pairing<T>>,
     pub size: usize,
     pub corrupted: usize,
+    /// Like [`TopHeap::bound`].
+    pub bound: usize,
 }
 
+/// Default for [`LateHeap::bound`].  Smaller bounds let corruption run away, see
+/// `plan.markdown`.
+pub const LATE_DEFAULT_BOUND: usize = 32;
+
 impl<T> Default for LateHeap<T> {
     fn default() -> Self {
+        Self::new(LATE_DEFAULT_BOUND)
+    }
+}
+
+impl<T> LateHeap<T> {
+    /// # Panics
+    ///
+    /// Panics if `bound` is zero.
+    #[must_use]
+    pub fn new(bound: usize) -> Self {
+        assert!(bound > 0, "bound has to be positive");
         Self {
             root: None,
             size: 0,
             corrupted: 0,
+            bound,
         }
     }
+
+    pub fn count_corrupted(&self) -> usize {
+        self.corrupted
+    }
+    /// Includes the items that wait to be witnessed, see [`LateHeap::count_delayed_corruption`].
+    pub fn count_uncorrupted(&self) -> usize {
+        self.size - self.corrupted
+    }
+    /// Items selected for corruption, that haven't been witnessed yet.  Walks the whole heap.
+    pub fn count_delayed_corruption(&self) -> usize {
+        self.root
+            .as_ref()
+            .map_or(0, Pairing::count_delayed_corruption)
+    }
+    pub fn is_empty(&self) -> bool {
+        debug_assert_eq!(self.size > 0, self.root.is_some());
+        self.root.is_none()
+    }
 }
 
 impl<T: Ord> LateHeap<T> {
     #[must_use]
     pub fn insert(self, item: T) -> Self {
-        match self.root {
-            None => Self {
-                root: Some(Pairing::new(item)),
-                size: 1,
-                corrupted: 0,
-            },
-            Some(root) => Self {
-                root: Some(root.insert(item)),
-                size: self.size + 1,
-                corrupted: self.corrupted,
-            },
+        let root = Pairing::meld_option(self.root, Some(Pairing::new(item)));
+        Self {
+            root,
+            size: self.size + 1,
+            ..self
         }
     }
+
     #[must_use]
     pub fn meld(self, other: Self) -> Self {
         let root = Pairing::meld_option(self.root, other.root);
@@ -1089,25 +1510,619 @@ impl<T: Ord> LateHeap<T> {
             root,
             size: self.size + other.size,
             corrupted: self.corrupted + other.corrupted,
+            ..self
         }
     }
+
+    /// Witnesses whatever the root has been hiding: pools it under the root's key, and hands it
+    /// out to be reported.
+    fn witness_root(mut self) -> (Self, Vec<T>) {
+        let Some(root) = self.root.as_mut() else {
+            return (self, vec![]);
+        };
+        let witnessed = mem::take(&mut root.witnessed);
+        root.key.count += witnessed.count;
+        self.corrupted += witnessed.count;
+        (self, Vec::from(witnessed))
+    }
+
+    /// Removes one item with the smallest apparent key.
+    ///
+    /// Returns the item, unless it's one of the corrupted items pooled at the root, and the
+    /// items that the root had been hiding, which are corrupted now.
     #[must_use]
     pub fn pop_min(self) -> (Self, Option<T>, Vec<T>) {
-        // TODO: simplify.
-        match self.root {
-            None => (Self::default(), None, vec![]),
-            Some(_root) => {
-                todo!()
-            }
+        let (me, corrupted) = self.witness_root();
+        let Some(Pairing { key, children, .. }) = me.root else {
+            return (me, None, corrupted);
+        };
+        match key.delete_one() {
+            Ok(key) => (
+                Self {
+                    root: Some(Pairing {
+                        key,
+                        witnessed: WitnessedSet::default(),
+                        children,
+                    }),
+                    size: me.size - 1,
+                    corrupted: me.corrupted - 1,
+                    ..me
+                },
+                None,
+                corrupted,
+            ),
+            Err(item) => (
+                Self {
+                    root: Pairing::merge_children_late(children.into_vec(), me.bound),
+                    size: me.size - 1,
+                    ..me
+                },
+                Some(item),
+                corrupted,
+            ),
         }
     }
+
+    /// Removes the root item together with all the corrupted items pooled with it, including
+    /// the ones that the root had been hiding.
     #[must_use]
     pub fn heavy_pop_min(self) -> (Self, Option<T>, Vec<T>) {
-        match self.root {
-            None => (Self::default(), None, vec![]),
-            Some(_root) => {
-                todo!()
+        let (me, corrupted) = self.witness_root();
+        let Some(Pairing { key, children, .. }) = me.root else {
+            return (me, None, corrupted);
+        };
+        (
+            Self {
+                root: Pairing::merge_children_late(children.into_vec(), me.bound),
+                size: me.size - key.count - 1,
+                corrupted: me.corrupted - key.count,
+                ..me
+            },
+            Some(key.item),
+            corrupted,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::{prop_assert, prop_assert_eq, proptest};
+    use proptest::test_runner::TestCaseError;
+    use rand::seq::SliceRandom;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn late_heap_corrupts_late() {
+        let mut items: Vec<u32> = (0..10_000).collect();
+        items.shuffle(&mut rand::rng());
+        let mut top = TopHeap::new(LATE_DEFAULT_BOUND);
+        let mut late = LateHeap::default();
+        for &x in &items {
+            top = top.insert(x);
+            late = late.insert(x);
+        }
+        let (mut top_reported, mut late_reported) = (0, 0);
+        let mut behind = false;
+        for _ in 0..items.len() / 2 {
+            let (rest, _, corrupted) = top.pop_min();
+            top = rest;
+            top_reported += corrupted.len();
+            let (rest, _, corrupted) = late.pop_min();
+            late = rest;
+            late_reported += corrupted.len();
+            assert!(late.count_corrupted() <= top.count_corrupted());
+            assert!(
+                late_reported <= top_reported,
+                "{late_reported} {top_reported}"
+            );
+            behind |= late.count_corrupted() < top.count_corrupted();
+        }
+        assert!(behind);
+    }
+
+    #[test]
+    fn deep_tree() {
+        // Descending inserts build a linked list.  Deep enough to overflow the default test
+        // thread stack, if anything recursed once per level.
+        const N: usize = 1_000_000;
+        let mut heap = SoftHeap::new(4);
+        heap.extend((0..N).rev());
+        let root = heap.root.as_ref().unwrap();
+        assert_eq!(root.count_uncorrupted(), N);
+        assert_eq!(root.count_corrupted(), 0);
+        assert_eq!(root.count_delayed_corruption(), 0);
+        let copy = heap.clone();
+        assert_eq!(copy, heap);
+        assert!(format!("{heap:?}").len() > N);
+        let different = copy.insert(N);
+        assert_ne!(different, heap);
+        assert_eq!(heap.cmp(&heap.clone()), Ordering::Equal);
+        assert_eq!(heap.cmp(&different), Ordering::Less);
+        drop(heap);
+        drop(different);
+
+        let chain = || {
+            let mut witnessed = Witnessed::singleton(0);
+            for i in 1..N {
+                let mut parent = Witnessed::singleton(i);
+                parent.add_child(witnessed);
+                witnessed = parent;
             }
+            witnessed
+        };
+        assert_eq!(Vec::from(chain()), (0..N).rev().collect::<Vec<_>>());
+        let witnessed = chain();
+        let copy = witnessed.clone();
+        assert_eq!(copy, witnessed);
+        assert_eq!(copy.cmp(&witnessed), Ordering::Equal);
+        assert_ne!(copy, Witnessed::singleton(0));
+        assert!(format!("{witnessed:?}").len() > N);
+    }
+
+    #[test]
+    fn debug_like_derived() {
+        let mut witnessed = WitnessedSet::default();
+        witnessed.add_child(Witnessed::singleton(7));
+        let pairing = Pairing {
+            key: Pool::new(1),
+            witnessed,
+            children: Children::from(vec![Pairing::new(2), Pairing::new(3)]),
+        };
+        assert_eq!(
+            format!("{pairing:?}"),
+            "Pairing { key: Pool { item: 1, count: 0 }, \
+             witnessed: WitnessedSet { count: 1, items: [Witnessed { item: 7, count: 1, \
+             children: WitnessedSet { count: 0, items: [] } }] }, \
+             children: Children([\
+             Pairing { key: Pool { item: 2, count: 0 }, \
+             witnessed: WitnessedSet { count: 0, items: [] }, children: Children([]) }, \
+             Pairing { key: Pool { item: 3, count: 0 }, \
+             witnessed: WitnessedSet { count: 0, items: [] }, children: Children([]) }\
+             ]) }"
+        );
+    }
+
+    /// What `derive` would have made of a `Witnessed`.
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    struct DerivedWitnessed {
+        item: u8,
+        count: usize,
+        children_count: usize,
+        children: Vec<DerivedWitnessed>,
+    }
+
+    /// What `derive` would have made of a `Pairing`.
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    struct DerivedPairing {
+        key: Pool<u8>,
+        witnessed_count: usize,
+        witnessed: Vec<DerivedWitnessed>,
+        children: Vec<DerivedPairing>,
+    }
+
+    fn derived_witnessed(set: &WitnessedSet<u8>) -> Vec<DerivedWitnessed> {
+        set.items
+            .iter()
+            .map(|witnessed| DerivedWitnessed {
+                item: witnessed.item,
+                count: witnessed.count,
+                children_count: witnessed.children.count,
+                children: derived_witnessed(&witnessed.children),
+            })
+            .collect()
+    }
+
+    fn derived(pairing: &Pairing<u8>) -> DerivedPairing {
+        DerivedPairing {
+            key: pairing.key.clone(),
+            witnessed_count: pairing.witnessed.count,
+            witnessed: derived_witnessed(&pairing.witnessed),
+            children: pairing.children.iter().map(derived).collect(),
+        }
+    }
+
+    fn run(ops: impl IntoIterator<Item = Option<u8>>) -> SoftHeap<u8> {
+        let mut heap = SoftHeap::new(2);
+        for op in ops {
+            match op {
+                Some(x) => heap.push(x),
+                None => drop(heap.pop()),
+            }
+        }
+        heap
+    }
+
+    proptest! {
+        #[test]
+        fn ord_like_derived(
+            prefix in proptest::collection::vec(proptest::option::of(0..3_u8), 0..60),
+            a in proptest::collection::vec(proptest::option::of(0..3_u8), 0..10),
+            b in proptest::collection::vec(proptest::option::of(0..3_u8), 0..10),
+        ) {
+            // A shared prefix, so that the heaps often only differ deep down.
+            let a = run(prefix.iter().copied().chain(a));
+            let b = run(prefix.iter().copied().chain(b));
+            let expected = a.root.as_ref().map(derived).cmp(&b.root.as_ref().map(derived));
+            prop_assert_eq!(a.root.cmp(&b.root), expected);
+            prop_assert_eq!(a.root.partial_cmp(&b.root), Some(expected));
+        }
+    }
+
+    #[test]
+    fn in_place_matches_consuming() {
+        let mut consuming = SoftHeap::new(3);
+        let mut in_place = SoftHeap::new(3);
+        for i in (0..1000).rev() {
+            consuming = consuming.insert(i);
+            in_place.push(i);
+            if i % 3 == 0 {
+                let (new_consuming, item, corrupted) = consuming.pop_min();
+                consuming = new_consuming;
+                assert_eq!(in_place.pop(), (item, corrupted));
+            }
+        }
+        assert_eq!(in_place, consuming);
+        assert_eq!(
+            in_place.peek(),
+            consuming.root.as_ref().map(|r| &r.key.item)
+        );
+    }
+
+    #[test]
+    fn append() {
+        let mut a = SoftHeap::new(4);
+        let mut b = SoftHeap::new(4);
+        a.extend([5, 3, 8]);
+        b.extend([1, 9]);
+        a.append(&mut b);
+        assert!(b.is_empty());
+        assert_eq!(a.size, 5);
+        assert_eq!(a.peek(), Some(&1));
+        assert_eq!(a.pop(), (Some(1), vec![]));
+    }
+
+    #[test]
+    fn epsilon_round_trip() {
+        for epsilon in [1.0, 0.5, 1.0 / 3.0, 0.25, 0.1, 0.01] {
+            let n = corrupt_every_n_for(epsilon);
+            assert!(estimated_epsilon(n) <= epsilon, "{epsilon} {n}");
+            assert!(
+                estimated_epsilon(n - 1) > epsilon || n <= 4,
+                "{epsilon} {n}"
+            );
+            assert_eq!(SoftHeap::<u8>::with_epsilon(epsilon).corrupt_every_n, n);
+        }
+        assert_eq!(corrupt_every_n_for(f64::MIN_POSITIVE), usize::MAX);
+        for bad in [0.0, -0.5, 1.5, f64::NAN, f64::INFINITY] {
+            assert!(
+                std::panic::catch_unwind(|| corrupt_every_n_for(bad)).is_err(),
+                "{bad}"
+            );
+        }
+    }
+
+    /// Worst `corrupted / inserted` at any one time, for inserting `items` with one `pop_min`
+    /// after every `pop_every` inserts, and then popping everything.
+    #[allow(clippy::cast_precision_loss)]
+    fn worst_corruption(corrupt_every_n: usize, items: &[u32], pop_every: usize) -> f64 {
+        let mut heap = SoftHeap::new(corrupt_every_n);
+        let mut worst: f64 = 0.0;
+        for (i, &item) in items.iter().enumerate() {
+            heap = heap.insert(item);
+            if (i + 1) % pop_every == 0 {
+                heap = heap.pop_min().0;
+                worst = worst.max(heap.corrupted as f64 / heap.inserted as f64);
+            }
+        }
+        while !heap.is_empty() {
+            heap = heap.pop_min().0;
+            worst = worst.max(heap.corrupted as f64 / heap.inserted as f64);
+        }
+        worst
+    }
+
+    #[test]
+    fn estimated_epsilon_holds() {
+        let ascending: Vec<u32> = (0..20_000).collect();
+        let mut shuffled = ascending.clone();
+        shuffled.shuffle(&mut rand::rng());
+        for corrupt_every_n in 4..=12 {
+            for items in [&ascending, &shuffled] {
+                for pop_every in [1, 3, 10, usize::MAX] {
+                    let worst = worst_corruption(corrupt_every_n, items, pop_every);
+                    assert!(
+                        worst <= estimated_epsilon(corrupt_every_n),
+                        "{corrupt_every_n} {pop_every} {worst}"
+                    );
+                }
+            }
+        }
+        // Why we don't estimate anything for `corrupt_every_n = 4`, even though
+        // `schubert::tests::test_bounded_corruption` stays under a third.
+        assert!(worst_corruption(4, &ascending, usize::MAX) <= 1.0 / 3.0);
+        assert!(worst_corruption(4, &shuffled, 10) > 0.5);
+    }
+
+    #[test]
+    fn budget_guard() {
+        let mut heap = SoftHeap::with_epsilon(0.25).guarded();
+        heap.extend(0..1_000);
+        let (mut heap, _, _) = heap.try_pop_min().unwrap();
+        assert!(heap.count_corrupted() > 0);
+        while !heap.is_empty() {
+            (heap, _, _) = heap.try_pop_min().unwrap();
+        }
+
+        // Pretend we inserted less than we did, to blow the budget.
+        let mut heap = SoftHeap::with_epsilon(0.25).guarded();
+        heap.extend(0..1_000);
+        heap.inserted = 0;
+        let corrupt_every_n = heap.corrupt_every_n;
+        let error = heap.try_pop_min().unwrap_err();
+        assert_eq!(error.inserted, 0);
+        assert_eq!(error.corrupt_every_n, corrupt_every_n);
+        assert!(error.corrupted > 0);
+
+        let mut heap = SoftHeap::with_epsilon(0.25);
+        heap.extend(0..1_000);
+        heap.inserted = 0;
+        assert!(heap.try_pop_min().is_ok());
+    }
+
+    #[test]
+    fn budget_guard_everywhere() {
+        let mut heap = SoftHeap::with_epsilon(0.25).guarded();
+        heap.extend(0..1_000);
+        while !heap.is_empty() {
+            heap.try_heavy_pop().unwrap();
+            heap.try_pop().unwrap();
+            (heap, _, _) = heap.try_heavy_pop_min().unwrap();
+        }
+
+        let mut heap = SoftHeap::with_epsilon(0.25).guarded();
+        heap.extend(0..1_000);
+        heap.inserted = 0;
+        assert!(heap.clone().try_heavy_pop_min().is_err());
+        let size = heap.size;
+        assert!(heap.clone().try_pop().is_err());
+        assert!(heap.try_heavy_pop().is_err());
+        // The failed pop still happened, and left the heap behind.
+        assert!(heap.size < size);
+    }
+
+    /// Most corrupted items at any one time, for `n` shuffled inserts with one `pop_min` per
+    /// three inserts, and then popping everything.  Like the sweeps in `plan.markdown`.
+    fn top_heap_most_corrupted(n: usize, bound: usize) -> usize {
+        let mut items: Vec<usize> = (0..n).collect();
+        items.shuffle(&mut rand::rng());
+        let mut heap = TopHeap::new(bound);
+        let mut most = 0;
+        for (i, &item) in items.iter().enumerate() {
+            heap = heap.insert(item);
+            if i % 3 == 2 {
+                heap = heap.pop_min().0;
+                most = most.max(heap.count_corrupted());
+            }
+        }
+        while !heap.is_empty() {
+            heap = heap.pop_min().0;
+            most = most.max(heap.count_corrupted());
+        }
+        most
+    }
+
+    #[test]
+    fn top_heap_corruption() {
+        const N: usize = 100_000;
+        let most = top_heap_most_corrupted(N, 32);
+        assert!(most <= N / 100, "{most}");
+    }
+
+    #[test]
+    fn top_heap_small_bounds() {
+        const N: usize = 10_000;
+        let most = top_heap_most_corrupted(N, 16);
+        assert!(most <= N / 100, "{most}");
+        // Small bounds still work, but corruption runs away.
+        let most = top_heap_most_corrupted(N, 4);
+        assert!(most >= N / 2, "{most}");
+        assert!(std::panic::catch_unwind(|| TopHeap::<u8>::new(0)).is_err());
+        assert!(std::panic::catch_unwind(|| LateHeap::<u8>::new(0)).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn top_heap_keeps_order(
+            ops in proptest::collection::vec(proptest::option::of(0..100_u8), 0..2_000),
+            bound in 1..64_usize,
+        ) {
+            let mut heap = TopHeap::new(bound);
+            let mut uncorrupted = BTreeSet::new();
+            for (index, op) in ops.into_iter().enumerate() {
+                if let Some(x) = op {
+                    heap = heap.insert((x, index));
+                    uncorrupted.insert((x, index));
+                } else {
+                    let (new_heap, item, corrupted) = heap.pop_min();
+                    heap = new_heap;
+                    for c in corrupted {
+                        prop_assert!(uncorrupted.remove(&c));
+                    }
+                    if let Some(item) = item {
+                        prop_assert_eq!(uncorrupted.pop_first(), Some(item));
+                    }
+                }
+                prop_assert_eq!(heap.count_uncorrupted(), uncorrupted.len());
+            }
+            let mut left = Vec::from(heap);
+            left.sort_unstable();
+            prop_assert_eq!(left, uncorrupted.into_iter().collect::<Vec<_>>());
+        }
+
+        #[test]
+        fn late_heap_follows_top_heap(
+            ops in proptest::collection::vec(proptest::option::of(0..100_u8), 0..2_000),
+            bound in 1..64_usize,
+        ) {
+            let mut top = TopHeap::new(bound);
+            let mut late = LateHeap::new(bound);
+            let mut top_reported = BTreeSet::new();
+            let mut late_reported = BTreeSet::new();
+            let mut check = |top: &TopHeap<_>,
+                             late: &LateHeap<_>,
+                             (top_item, top_corrupted): (Option<(u8, usize)>, Vec<_>),
+                             (late_item, late_corrupted): (Option<(u8, usize)>, Vec<_>)|
+             -> Result<(), TestCaseError> {
+                prop_assert_eq!(top_item, late_item);
+                top_reported.extend(top_corrupted);
+                late_reported.extend(late_corrupted);
+                prop_assert!(late_reported.is_subset(&top_reported));
+                prop_assert_eq!(late.size, top.size);
+                prop_assert_eq!(
+                    late.count_corrupted() + late.count_delayed_corruption(),
+                    top.count_corrupted()
+                );
+                Ok(())
+            };
+            for (index, op) in ops.into_iter().enumerate() {
+                if let Some(x) = op {
+                    top = top.insert((x, index));
+                    late = late.insert((x, index));
+                } else {
+                    let (new_top, top_item, top_corrupted) = top.pop_min();
+                    let (new_late, late_item, late_corrupted) = late.pop_min();
+                    (top, late) = (new_top, new_late);
+                    check(&top, &late, (top_item, top_corrupted), (late_item, late_corrupted))?;
+                }
+            }
+            while !top.is_empty() {
+                let (new_top, top_item, top_corrupted) = top.heavy_pop_min();
+                let (new_late, late_item, late_corrupted) = late.heavy_pop_min();
+                (top, late) = (new_top, new_late);
+                check(&top, &late, (top_item, top_corrupted), (late_item, late_corrupted))?;
+            }
+            prop_assert!(late.is_empty());
+            prop_assert_eq!(late_reported, top_reported);
+        }
+
+        #[test]
+        fn pop_events_bound_every_item(
+            ops in proptest::collection::vec(proptest::option::of(0..100_u8), 0..2_000),
+        ) {
+            let mut heap = SoftHeap::with_epsilon(0.25);
+            let mut inserted = vec![];
+            let mut named = vec![];
+            let mut drained = 0;
+            let mut pooled = 0;
+            let mut pop = |heap: &mut SoftHeap<(u8, usize)>| -> Result<(), TestCaseError> {
+                for event in heap.pop_events() {
+                    match event {
+                        PopEvent::Popped(item) => named.push(item),
+                        PopEvent::PoolDrained { key, remaining } => {
+                            let root = heap.peek_min().unwrap();
+                            prop_assert_eq!((&root.item, root.count), (&key, remaining));
+                            drained += 1;
+                        }
+                        PopEvent::Corrupted { item, apparent_key } => {
+                            prop_assert!(item <= apparent_key);
+                            named.push(item);
+                            pooled += 1;
+                        }
+                        PopEvent::Witnessed(_) => {
+                            prop_assert!(false, "we never did a plain pop");
+                        }
+                    }
+                }
+                Ok(())
+            };
+            for (index, op) in ops.into_iter().enumerate() {
+                if let Some(x) = op {
+                    heap.push((x, index));
+                    inserted.push((x, index));
+                } else {
+                    pop(&mut heap)?;
+                }
+            }
+            while !heap.is_empty() {
+                pop(&mut heap)?;
+            }
+            named.sort_unstable();
+            inserted.sort_unstable();
+            prop_assert_eq!(named, inserted);
+            prop_assert_eq!(drained, pooled);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn pop_events_next_to_pop(
+            ops in proptest::collection::vec(proptest::option::of(0..100_u8), 0..2_000),
+        ) {
+            let mut plain = SoftHeap::new(4);
+            let mut events = SoftHeap::new(4);
+            // Everything each of them reported so far, popped or corrupted.
+            let mut plain_reported = BTreeSet::new();
+            let mut events_reported = BTreeSet::new();
+            let mut pop = |plain: &mut SoftHeap<(u8, usize)>,
+                           events: &mut SoftHeap<(u8, usize)>|
+             -> Result<(), TestCaseError> {
+                let pool = plain.peek_min().cloned();
+                let (item, corrupted) = plain.pop();
+                plain_reported.extend(item);
+                plain_reported.extend(corrupted);
+                for event in events.pop_events() {
+                    match event {
+                        PopEvent::Popped(popped) => {
+                            prop_assert_eq!(Some(popped), item);
+                            events_reported.insert(popped);
+                        }
+                        PopEvent::PoolDrained { key, remaining } => {
+                            let pool = pool.as_ref().unwrap();
+                            prop_assert_eq!(item, None);
+                            prop_assert_eq!((key, remaining + 1), (pool.item, pool.count));
+                        }
+                        PopEvent::Corrupted { item, .. } => {
+                            events_reported.insert(item);
+                        }
+                        PopEvent::Witnessed(_) => {
+                            prop_assert!(false, "we never did a plain pop on this heap");
+                        }
+                    }
+                }
+                // Both do the same merges, but the events report corrupted items no later.
+                prop_assert_eq!(plain.peek_min(), events.peek_min());
+                prop_assert_eq!(plain.size, events.size);
+                prop_assert!(plain_reported.is_subset(&events_reported));
+                Ok(())
+            };
+            for (index, op) in ops.into_iter().enumerate() {
+                if let Some(x) = op {
+                    plain.push((x, index));
+                    events.push((x, index));
+                } else {
+                    pop(&mut plain, &mut events)?;
+                }
+            }
+            while !plain.is_empty() {
+                pop(&mut plain, &mut events)?;
+            }
+            prop_assert!(events.is_empty());
+            prop_assert_eq!(plain_reported, events_reported);
+        }
+    }
+
+    #[test]
+    fn peek_min_predicts_pop_min() {
+        let mut heap = SoftHeap::new(2);
+        assert!(heap.peek_min().is_none());
+        assert!(!heap.next_pop_is_item());
+        heap.extend((0..1000).rev());
+        while let Some(&Pool { item, count }) = heap.peek_min() {
+            let real = heap.next_pop_is_item();
+            assert_eq!(real, count == 0);
+            let (popped, _corrupted) = heap.pop();
+            assert_eq!(popped, real.then_some(item));
         }
     }
 }
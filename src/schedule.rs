@@ -0,0 +1,225 @@
+// Unit-task deadline scheduling, aka "Alice's homework".
+//
+// Every task takes exactly one day.  A task with deadline `d` is on time, if it's done on one of
+// the days `0..d`.  Missing a deadline costs the task's penalty, no matter how late the task ends
+// up.  We want to minimise the total penalty of late tasks.
+
+use std::error::Error;
+use std::fmt::{Debug, Display};
+use std::iter::repeat_n;
+
+use crate::schubert::{linear_loop, Operation};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    /// `days[d]` is the index of the task done on day `d`.
+    /// On-time tasks come first, ordered by deadline, followed by all late tasks.
+    pub days: Vec<usize>,
+    /// Indices of the tasks that miss their deadline.
+    pub late: Vec<usize>,
+}
+
+/// Bucket-sorts task indices by deadline.
+///
+/// Deadlines beyond the number of tasks are as good as no deadline at all, so we cap them at `n`.
+/// That gives us `n + 1` buckets, and thus linear time.
+#[must_use]
+pub fn bucket_by_deadline<P>(tasks: &[(usize, P)]) -> Vec<Vec<usize>> {
+    let n = tasks.len();
+    let mut buckets = vec![vec![]; n + 1];
+    for (index, (deadline, _)) in tasks.iter().enumerate() {
+        buckets[(*deadline).min(n)].push(index);
+    }
+    buckets
+}
+
+/// Reduces a scheduling instance to a sequence of heap operations.
+///
+/// This is the forward pass from the write-up: going forward through the days, we insert each
+/// task on its deadline, and then delete the cheapest tasks until no more tasks are left than
+/// there were days so far.  The tasks left in the heap at the end are exactly the ones that can
+/// be done on time.
+///
+/// Items are `(penalty, index)`, so that ties in penalties are broken consistently.
+#[must_use]
+pub fn to_operations<P: Clone>(tasks: &[(usize, P)]) -> Vec<Operation<(P, usize)>> {
+    let mut ops = vec![];
+    let mut size: usize = 0;
+    for (day, bucket) in bucket_by_deadline(tasks).into_iter().enumerate() {
+        size += bucket.len();
+        ops.extend(
+            bucket
+                .into_iter()
+                .map(|index| Operation::Insert((tasks[index].1.clone(), index))),
+        );
+        let deletes = size.saturating_sub(day);
+        ops.extend(repeat_n(Operation::DeleteMin, deletes));
+        size -= deletes;
+    }
+    ops
+}
+
+/// A task that was supposed to be on time, but can't be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Infeasible {
+    /// Index of the task.
+    pub task: usize,
+    /// The earliest day still free when the task's turn came up.
+    pub day: usize,
+}
+
+impl Display for Infeasible {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "task {} can't be done on time, the earliest free day is {}",
+            self.task, self.day
+        )
+    }
+}
+
+impl Error for Infeasible {}
+
+/// Why [`reconstruct`] can't build a schedule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleError {
+    Infeasible(Infeasible),
+    /// `on_time` names a task index that's out of range.
+    UnknownTask { task: usize },
+}
+
+impl From<Infeasible> for ScheduleError {
+    fn from(infeasible: Infeasible) -> Self {
+        Self::Infeasible(infeasible)
+    }
+}
+
+impl Display for ScheduleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Infeasible(infeasible) => write!(f, "{infeasible}"),
+            Self::UnknownTask { task } => write!(f, "there is no task {task}"),
+        }
+    }
+}
+
+impl Error for ScheduleError {}
+
+/// Rebuilds a complete schedule from just knowing which tasks are on time.
+///
+/// Runs in linear time and never looks at penalties: it only bucket-sorts the on-time tasks by
+/// deadline, and does them earliest deadline first.  That never misses a deadline, unless the
+/// set of on-time tasks is infeasible to begin with.
+///
+/// `on_time` takes task indices, eg the second components of the items [`linear_loop`] returns
+/// for [`to_operations`].
+pub fn reconstruct<P>(
+    tasks: &[(usize, P)],
+    on_time: impl IntoIterator<Item = usize>,
+) -> Result<Schedule, ScheduleError> {
+    let mut is_on_time = vec![false; tasks.len()];
+    for task in on_time {
+        *is_on_time
+            .get_mut(task)
+            .ok_or(ScheduleError::UnknownTask { task })? = true;
+    }
+
+    let (mut days, late): (Vec<usize>, Vec<usize>) = bucket_by_deadline(tasks)
+        .into_iter()
+        .flatten()
+        .partition(|&task| is_on_time[task]);
+    if let Some((day, &task)) = days
+        .iter()
+        .enumerate()
+        .find(|&(day, &task)| tasks[task].0 <= day)
+    {
+        return Err(Infeasible { task, day }.into());
+    }
+    days.extend(&late);
+    Ok(Schedule { days, late })
+}
+
+/// Finds a schedule that minimises the total penalty of late tasks.
+///
+/// Takes `(deadline, penalty)` pairs and runs in linear time, using [`linear_loop`] to figure out
+/// which tasks make their deadlines.
+#[must_use]
+pub fn schedule<P: Ord + Debug + Clone>(tasks: &[(usize, P)]) -> Schedule {
+    let on_time = linear_loop(to_operations(tasks))
+        .into_iter()
+        .map(|(_penalty, task)| task);
+    reconstruct(tasks, on_time).expect("linear_loop only keeps tasks that fit")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::{prop_assert, prop_assert_eq, proptest};
+
+    /// The classic greedy algorithm: consider tasks by descending penalty, and keep each one
+    /// that still fits.  Takes `O(n^2)`, but it's obviously correct.
+    fn greedy_late_penalty(tasks: &[(usize, u32)]) -> u32 {
+        let mut order: Vec<usize> = (0..tasks.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(tasks[i].1));
+        let mut kept: Vec<usize> = vec![];
+        let mut late = 0;
+        for i in order {
+            let deadline = tasks[i].0.min(tasks.len());
+            kept.push(deadline);
+            kept.sort_unstable();
+            if !kept.iter().enumerate().all(|(day, &d)| day < d) {
+                let position = kept.iter().position(|&d| d == deadline).unwrap();
+                kept.remove(position);
+                late += tasks[i].1;
+            }
+        }
+        late
+    }
+
+    #[test]
+    fn homework() {
+        let tasks = [(1, 10), (1, 20), (2, 5), (2, 30), (0, 7)];
+        let Schedule { days, mut late } = schedule(&tasks);
+        late.sort_unstable();
+        assert_eq!(late, vec![0, 2, 4]);
+        assert_eq!(&days[..2], &[1, 3]);
+    }
+
+    #[test]
+    fn reconstruct_rejects_infeasible() {
+        let tasks = [(1, 10), (1, 20), (3, 5)];
+        assert_eq!(
+            reconstruct(&tasks, [0, 1]),
+            Err(ScheduleError::Infeasible(Infeasible { task: 1, day: 1 }))
+        );
+        assert_eq!(
+            reconstruct(&tasks, [1, 3]),
+            Err(ScheduleError::UnknownTask { task: 3 })
+        );
+        let Schedule { days, late } = reconstruct(&tasks, [1, 2]).unwrap();
+        assert_eq!(days, vec![1, 2, 0]);
+        assert_eq!(late, vec![0]);
+    }
+
+    proptest! {
+        #[test]
+        fn schedule_is_optimal(
+            tasks in proptest::collection::vec((0..50_usize, 0..1_000_u32), 0..200)
+        ) {
+            let Schedule { days, late } = schedule(&tasks);
+
+            let mut all = days.clone();
+            all.sort_unstable();
+            prop_assert_eq!(all, (0..tasks.len()).collect::<Vec<_>>());
+
+            let on_time = days.len() - late.len();
+            prop_assert_eq!(&days[on_time..], &late[..]);
+            for (day, &index) in days[..on_time].iter().enumerate() {
+                prop_assert!(day < tasks[index].0);
+            }
+
+            let penalty: u32 = late.iter().map(|&i| tasks[i].1).sum();
+            prop_assert_eq!(penalty, greedy_late_penalty(&tasks));
+        }
+    }
+}
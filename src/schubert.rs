@@ -1,7 +1,9 @@
-use itertools::chain;
-
 // Schubert matroids.
-use crate::pairing::SoftHeap;
+use crate::entry::{Entry, KeyValueHeap};
+use crate::pairing::{corrupt_every_n_for, SoftHeap};
+use std::collections::BinaryHeap;
+use std::error::Error;
+use std::fmt::Display;
 use std::option::Option;
 use std::{cmp::Reverse, fmt::Debug};
 
@@ -9,6 +11,7 @@ use std::{cmp::Reverse, fmt::Debug};
 pub enum Operation<T> {
     Insert(T),
     DeleteMin,
+    DeleteMax,
 }
 
 /// Name for Haskell's `sequenceA :: Applicative f => t (f a) -> f (t a) `
@@ -16,6 +19,7 @@ pub fn sequence<T>(ops: Operation<Option<T>>) -> Option<Operation<T>> {
     match ops {
         Operation::Insert(x) => x.map(Operation::Insert),
         Operation::DeleteMin => Some(Operation::DeleteMin),
+        Operation::DeleteMax => Some(Operation::DeleteMax),
     }
 }
 
@@ -34,6 +38,7 @@ impl<T> Operation<T> {
         match self {
             Self::Insert(x) => Operation::Insert(f(x)),
             Self::DeleteMin => Operation::DeleteMin,
+            Self::DeleteMax => Operation::DeleteMax,
         }
     }
 
@@ -41,13 +46,70 @@ impl<T> Operation<T> {
         match *self {
             Self::Insert(ref x) => Operation::Insert(x),
             Self::DeleteMin => Operation::DeleteMin,
+            Self::DeleteMax => Operation::DeleteMax,
+        }
+    }
+
+    /// Swaps `DeleteMin` and `DeleteMax`, and leaves inserts alone.
+    #[must_use]
+    pub fn flip(self) -> Self {
+        match self {
+            Self::DeleteMin => Self::DeleteMax,
+            Self::DeleteMax => Self::DeleteMin,
+            insert @ Self::Insert(_) => insert,
         }
     }
 }
 
+/// `ops` mixes `DeleteMin` and `DeleteMax`.
+///
+/// Mixed sequences don't describe a heap matroid, so none of our machinery applies to them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MixedDeletes;
+
+impl Display for MixedDeletes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "operations mix DeleteMin and DeleteMax")
+    }
+}
+
+impl Error for MixedDeletes {}
+
+/// Whether `ops` deletes maxima instead of minima.
+///
+/// A sequence without any deletes counts as min-deleting.
+pub fn deletes_max<T>(ops: &[Operation<T>]) -> Result<bool, MixedDeletes> {
+    let max = ops.iter().any(|op| matches!(op, Operation::DeleteMax));
+    if max && ops.iter().any(|op| matches!(op, Operation::DeleteMin)) {
+        return Err(MixedDeletes);
+    }
+    Ok(max)
+}
+
+/// Like [`deletes_max`], for the helpers that can't return an error.
+fn direction<T>(ops: &[Operation<T>]) -> bool {
+    deletes_max(ops).unwrap_or_else(|mixed| panic!("{mixed}"))
+}
+
+/// Deleting the maximum of some items is the same as deleting the minimum of the reversed items.
+///
+/// Flips the direction of all deletes, and wraps all items in `Reverse` to match.
+#[must_use]
+pub fn reverse_ops<T>(ops: Vec<Operation<T>>) -> Vec<Operation<Reverse<T>>> {
+    ops.into_iter().map(|op| op.map(Reverse).flip()).collect()
+}
+
+/// Undoes [`reverse_ops`].
+#[must_use]
+pub fn unreverse_ops<T>(ops: Vec<Operation<Reverse<T>>>) -> Vec<Operation<T>> {
+    ops.into_iter()
+        .map(|op| op.map(|Reverse(x)| x).flip())
+        .collect()
+}
+
 pub fn count_deletes<T>(ops: &[Operation<T>]) -> usize {
     ops.iter()
-        .filter(|op| matches!(op, Operation::DeleteMin))
+        .filter(|op| matches!(op, Operation::DeleteMin | Operation::DeleteMax))
         .count()
 }
 
@@ -57,9 +119,42 @@ pub fn count_inserts<T>(ops: &[Operation<T>]) -> usize {
         .count()
 }
 
+/// Tags every inserted item with the index of its insert.  Doesn't compare any items.
+///
+/// # Ties
+///
+/// Equal items are ordered by when they were inserted: the earlier insert counts as the smaller
+/// item.  So a min-heap deletes the earliest of equal items first, and a max-heap the latest.
+///
+/// The tagged items are all distinct, so the primal and the dual agree on which item is which.
+/// That's what makes it safe to combine their results, eg in [`linear_loop`], even when there
+/// are ties.
+#[must_use]
+pub fn tag_ops<T>(ops: Vec<Operation<T>>) -> Vec<Operation<(T, usize)>> {
+    let mut index = 0;
+    ops.into_iter()
+        .map(|op| {
+            op.map(|x| {
+                index += 1;
+                (x, index - 1)
+            })
+        })
+        .collect()
+}
+
+/// Dualising doesn't compare any items, so it doesn't care about ties.  But if you want to match
+/// up the items of the dual with the items of the primal, tag them with [`tag_ops`] first.
+///
+/// Keeps the direction of the deletes: the dual of a max-deleting sequence deletes maxima of the
+/// reversed items.
+///
+/// # Panics
+///
+/// Panics if `ops` mixes `DeleteMin` and `DeleteMax`.
 #[must_use]
 pub fn dualise_ops<T>(ops: Vec<Operation<T>>) -> Vec<Operation<Reverse<T>>> {
-    from_wrapped_ops(dualise_wrapped_ops(to_wrapped_ops(ops)))
+    let max = direction(&ops);
+    from_wrapped_ops(dualise_wrapped_ops(to_wrapped_ops(ops)), max)
 }
 
 /// This is equivalent to the formulation that we can create a nested by matroid by starting with an empty matroid
@@ -97,7 +192,7 @@ pub fn to_wrapped_ops<T>(ops: Vec<Operation<T>>) -> Vec<WrappedOp<T>> {
                 });
                 excess_deletes = excess_deletes.saturating_sub(1);
             }
-            Operation::DeleteMin => {
+            Operation::DeleteMin | Operation::DeleteMax => {
                 excess_deletes += 1;
             }
         }
@@ -106,12 +201,23 @@ pub fn to_wrapped_ops<T>(ops: Vec<Operation<T>>) -> Vec<WrappedOp<T>> {
     new_ops
 }
 
+/// Turns wrapped ops back into operations.
+///
+/// Wrapped ops don't know which way they delete, so the deletes come out as `DeleteMax` if `max`,
+/// and as `DeleteMin` otherwise.
 #[must_use]
-pub fn from_wrapped_ops<T>(ops: Vec<WrappedOp<T>>) -> Vec<Operation<T>> {
+pub fn from_wrapped_ops<T>(ops: Vec<WrappedOp<T>>, max: bool) -> Vec<Operation<T>> {
+    let delete = || {
+        if max {
+            Operation::DeleteMax
+        } else {
+            Operation::DeleteMin
+        }
+    };
     ops.into_iter()
         .flat_map(|WrappedOp { item, has_delete }| {
             if has_delete {
-                vec![Operation::Insert(item), Operation::DeleteMin]
+                vec![Operation::Insert(item), delete()]
             } else {
                 vec![Operation::Insert(item)]
             }
@@ -134,6 +240,10 @@ pub fn dualise_wrapped_ops<T>(ops: Vec<WrappedOp<T>>) -> Vec<WrappedOp<Reverse<T
 ///
 /// Logically speaking, dualising is its own inverse.  But we need to fix up the types, because Rust
 /// doesn't know that `Reverse<Reverse<T>>` is the same as `T`.
+///
+/// # Panics
+///
+/// Panics if `ops` mixes `DeleteMin` and `DeleteMax`.
 #[must_use]
 pub fn undualise_ops<T>(ops: Vec<Operation<Reverse<T>>>) -> Vec<Operation<T>> {
     dualise_ops(ops)
@@ -142,9 +252,34 @@ pub fn undualise_ops<T>(ops: Vec<Operation<Reverse<T>>>) -> Vec<Operation<T>> {
         .collect::<Vec<_>>()
 }
 
+/// Dualise without reversing the items.
+///
+/// The dual of a min-deleting sequence is a max-deleting sequence on the same items, and vice
+/// versa.  So this is what you want to model the backward pass.
+///
+/// A sequence without any deletes counts as min-deleting.
+///
+/// # Panics
+///
+/// Panics if `ops` mixes `DeleteMin` and `DeleteMax`.
+#[must_use]
+pub fn dual_ops<T>(ops: Vec<Operation<T>>) -> Vec<Operation<T>> {
+    let max = direction(&ops);
+    from_wrapped_ops(dualise_wrapped_ops(to_wrapped_ops(ops)), !max)
+        .into_iter()
+        .map(|op| op.map(|Reverse(x)| x))
+        .collect()
+}
+
+/// Keeps the direction of the deletes.
+///
+/// # Panics
+///
+/// Panics if `ops` mixes `DeleteMin` and `DeleteMax`.
 #[must_use]
 pub fn normalise_ops<T>(ops: Vec<Operation<T>>) -> Vec<Operation<T>> {
-    from_wrapped_ops(to_wrapped_ops(ops))
+    let max = direction(&ops);
+    from_wrapped_ops(to_wrapped_ops(ops), max)
 }
 
 /// Processes operations iteratively, alternating between primal and dual approaches.
@@ -153,97 +288,408 @@ pub fn normalise_ops<T>(ops: Vec<Operation<T>>) -> Vec<Operation<T>> {
 /// # Panics
 ///
 /// Panics if the operations list does not shrink by at least 1/6 of its size in each iteration.
-/// That's the case, when the soft heap corruption guarantee is violated.
+/// That's the case, when the soft heap corruption guarantee is violated.  See
+/// [`try_linear_loop`] for a version that doesn't panic.
+/// Also panics if `ops` mixes `DeleteMin` and `DeleteMax`.
+///
+/// Equal items are fine, see [`tag_ops`] for how we break ties.
 #[must_use]
-pub fn linear_loop<T: Ord + Debug + Clone>(ops: Vec<Operation<T>>) -> Vec<T> {
-    const CORRUPT_EVERY_N: usize = 16;
+pub fn linear_loop<T: Ord + Debug>(ops: Vec<Operation<T>>) -> Vec<T> {
+    try_linear_loop(ops, OnStall::Fail).unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// What [`try_linear_loop`] does, when an iteration doesn't shrink the instance enough.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnStall {
+    /// Give up and return the error.
+    Fail,
+    /// Finish the rest of the instance with an exact binary heap.  That takes `O(n log n)`, but
+    /// still gives the right answer.
+    Exact,
+}
+
+/// An iteration of [`try_linear_loop`] didn't shrink the instance enough.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stalled {
+    /// Counts from zero.
+    pub iteration: usize,
+    /// Inserts at the start of the iteration.
+    pub inserts: usize,
+    /// Deletes at the start of the iteration.
+    pub deletes: usize,
+    /// Inserts left at the end of the iteration.
+    pub remaining: usize,
+    /// `remaining / inserts`.  Should be at most 2/3.
+    pub shrink_ratio: f64,
+}
+
+impl Display for Stalled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "iteration {} with {} inserts and {} deletes left {} inserts, a shrink ratio of {}",
+            self.iteration, self.inserts, self.deletes, self.remaining, self.shrink_ratio
+        )
+    }
+}
+
+impl Error for Stalled {}
+
+/// Why [`try_linear_loop`] gave up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoopError {
+    Stalled(Stalled),
+    MixedDeletes(MixedDeletes),
+}
+
+impl From<Stalled> for LoopError {
+    fn from(stalled: Stalled) -> Self {
+        Self::Stalled(stalled)
+    }
+}
+
+impl From<MixedDeletes> for LoopError {
+    fn from(mixed: MixedDeletes) -> Self {
+        Self::MixedDeletes(mixed)
+    }
+}
+
+impl Display for LoopError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Stalled(stalled) => write!(f, "{stalled}"),
+            Self::MixedDeletes(mixed) => write!(f, "{mixed}"),
+        }
+    }
+}
+
+impl Error for LoopError {}
+
+/// Like [`linear_loop`], but returns an error instead of panicking, when an iteration doesn't
+/// shrink the instance by at least a third, or when `ops` mixes `DeleteMin` and `DeleteMax`.
+///
+/// With [`OnStall::Exact`], only returns an error for mixed deletes.
+pub fn try_linear_loop<T: Ord + Debug>(
+    ops: Vec<Operation<T>>,
+    on_stall: OnStall,
+) -> Result<Vec<T>, LoopError> {
+    let ops = tag_ops(ops);
+    let tagged = if deletes_max(&ops)? {
+        linear_loop_min(reverse_ops(ops), on_stall)?
+            .into_iter()
+            .map(|Reverse(x)| x)
+            .collect()
+    } else {
+        linear_loop_min(ops, on_stall)?
+    };
+    Ok(tagged.into_iter().map(|(x, _)| x).collect())
+}
+
+/// Error rate of the soft heaps in [`linear_loop`] and [`linear_loop_2`].
+///
+/// The primal pass needs at most 1/6 of the inserts corrupted, to shrink the instance by a third.
+const EPSILON: f64 = 1.0 / 8.0;
 
+fn linear_loop_min<T: Ord + Debug>(
+    ops: Vec<Operation<T>>,
+    on_stall: OnStall,
+) -> Result<Vec<T>, LoopError> {
+    linear_loop_min_with(corrupt_every_n_for(EPSILON), ops, on_stall)
+}
+
+fn linear_loop_min_with<T: Ord + Debug>(
+    corrupt_every_n: usize,
+    ops: Vec<Operation<T>>,
+    on_stall: OnStall,
+) -> Result<Vec<T>, LoopError> {
     // Normalising is not necessary, it just helps makes our debug asserts cleaner.
     // Normalising removes eg leading deletes, before anything has been inserted.
     let mut ops = normalise_ops(ops);
     let mut result = vec![];
 
-    while !ops.is_empty() {
+    for iteration in 0.. {
+        if ops.is_empty() {
+            break;
+        }
         let inserts = count_inserts(&ops);
         let deletes = count_deletes(&ops);
 
-        if deletes * 2 <= inserts {
-            // primal
-            let (left_ops, guaranteed_in) = approximate_heap(ops, CORRUPT_EVERY_N);
-            ops = left_ops;
-            result.extend(guaranteed_in);
-            assert!(count_inserts(&ops) <= inserts * 2 / 3);
-            assert!(count_inserts(&ops) <= inserts / 6 + deletes);
-            assert!(count_deletes(&ops) == deletes);
+        // Judge the inserts on borrowed items first, so that we still own the whole instance,
+        // if we have to fall back to the exact heap.
+        let primal = deletes * 2 <= inserts;
+        let (dropped, bound) = if primal {
+            let survivors = judge_by_ref(&ops, corrupt_every_n);
+            let bound = (inserts / 6 + deletes).min(inserts * 2 / 3);
+            (survivors, bound)
         } else {
-            // here we need to dualise.
-            let dual_ops = dualise_ops(ops);
-            let (left_over_ops, _guaranteed_out) = approximate_heap(dual_ops, CORRUPT_EVERY_N);
-            ops = undualise_ops(left_over_ops);
+            // here we need to dualise.  The dual sees the inserts in reverse order.
+            let dual = dualise_ops(ops.iter().map(Operation::as_ref).collect());
+            let mut guaranteed_out = judge_by_ref(&dual, corrupt_every_n);
+            guaranteed_out.reverse();
+            (guaranteed_out, inserts * 2 / 3)
+        };
+        let remaining = inserts - dropped.iter().filter(|&&d| d).count();
+        if remaining > bound {
+            return match on_stall {
+                OnStall::Fail => Err(Stalled {
+                    iteration,
+                    inserts,
+                    deletes,
+                    remaining,
+                    shrink_ratio: ratio(remaining, inserts),
+                }
+                .into()),
+                OnStall::Exact => {
+                    // `ops` is still the instance from the start of this iteration.
+                    result.extend(exact_heap(ops)?);
+                    Ok(result)
+                }
+            };
         }
-        debug_assert!(count_inserts(&ops) <= inserts * 2 / 3);
+        let mut dropped = dropped.into_iter();
+        ops = if primal {
+            ops.into_iter()
+                .filter_map(|op| match op {
+                    Operation::Insert(x) if dropped.next() == Some(true) => {
+                        result.push(x);
+                        None
+                    }
+                    op => Some(op),
+                })
+                .collect()
+        } else {
+            // Dropping guaranteed deletions is the dual of dropping guaranteed survivors.
+            let mut keep = dropped.rev().map(|d| !d);
+            undualise_ops(
+                dualise_ops(ops)
+                    .into_iter()
+                    .filter(|op| !matches!(op, Operation::Insert(_)) || keep.next() == Some(true))
+                    .collect(),
+            )
+        };
+        debug_assert!(!primal || count_deletes(&ops) == deletes);
         debug_assert!(count_deletes(&ops) <= count_inserts(&ops));
     }
-    result
+    Ok(result)
 }
 
-/// Processes operations iteratively, using primal and dual approaches.
+/// One soft heap pass over a min-deleting sequence.  Says for every insert, whether it's a
+/// guaranteed survivor.  Only borrows the items.
+fn judge_by_ref<T: Ord + Debug>(ops: &[Operation<T>], corrupt_every_n: usize) -> Vec<bool> {
+    approximate_heap_oracle_min(ops.iter().map(Operation::as_ref).collect(), corrupt_every_n)
+        .into_iter()
+        .filter_map(|op| match op {
+            Operation::Insert((_, judgement)) => Some(judgement == Judgement::Survivor),
+            Operation::DeleteMin | Operation::DeleteMax => None,
+        })
+        .collect()
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn ratio(a: usize, b: usize) -> f64 {
+    a as f64 / b as f64
+}
+
+/// Runs the operations on an exact binary heap, and returns what's left at the end.
+fn exact_heap<T: Ord>(ops: Vec<Operation<T>>) -> Result<Vec<T>, MixedDeletes> {
+    Ok(if deletes_max(&ops)? {
+        exact_heap_min(reverse_ops(ops))
+            .into_iter()
+            .map(|Reverse(x)| x)
+            .collect()
+    } else {
+        exact_heap_min(ops)
+    })
+}
+
+/// Like [`exact_heap`], after it made sure that all deletes are `DeleteMin`.
+fn exact_heap_min<T: Ord>(ops: Vec<Operation<T>>) -> Vec<T> {
+    let mut heap = BinaryHeap::new();
+    for op in ops {
+        match op {
+            Operation::Insert(x) => heap.push(Reverse(x)),
+            Operation::DeleteMin | Operation::DeleteMax => {
+                heap.pop();
+            }
+        }
+    }
+    heap.into_iter().map(|Reverse(x)| x).collect()
+}
+
+/// Processes operations iteratively, running the primal and dual approaches together in each round.
 /// Returns a vector of elements that are definitely in the heap at the end.
 ///
+/// Each round the primal pass nails down guaranteed survivors, and the dual pass nails down
+/// guaranteed deletions.  Both get removed from the instance, so the number of inserts shrinks
+/// to about `2 * epsilon` of its size per round, regardless of the ratio of inserts to deletes.
+///
 /// # Panics
 ///
-/// Panics if the operations list does not shrink by at least 1/6 of its size in each iteration.
+/// Panics if the number of inserts does not shrink by at least half in each iteration.
 /// That's the case, when the soft heap corruption guarantee is violated.
+/// Also panics if `ops` mixes `DeleteMin` and `DeleteMax`.
+///
+/// Equal items are fine, see [`tag_ops`] for how we break ties.
 #[must_use]
-pub fn linear_loop_2<T: Ord + Debug + Clone>(_ops: Vec<Operation<T>>) -> Vec<T> {
-    todo!()
+pub fn linear_loop_2<T: Ord + Debug>(ops: Vec<Operation<T>>) -> Vec<T> {
+    let ops = tag_ops(ops);
+    let tagged = if direction(&ops) {
+        linear_loop_2_min(reverse_ops(ops))
+            .into_iter()
+            .map(|Reverse(x)| x)
+            .collect()
+    } else {
+        linear_loop_2_min(ops)
+    };
+    tagged.into_iter().map(|(x, _)| x).collect()
 }
 
-#[must_use]
-pub fn approximate_heap_2<const CHUNKS: usize, T: Ord + Debug + Clone>(
-    _ops: Vec<Operation<T>>,
+fn linear_loop_2_min<T: Ord + Debug>(ops: Vec<Operation<T>>) -> Vec<T> {
+    const CORRUPT_EVERY_N: usize = corrupt_every_n_for(EPSILON);
+
+    let mut ops = normalise_ops(ops);
+    let mut result = vec![];
+
+    while !ops.is_empty() {
+        let inserts = count_inserts(&ops);
+
+        // Survivors are definitely in the final heap: report them and drop their inserts.
+        // Deleted items get flagged, so we can drop them in the dual below.
+        let flagged: Vec<Operation<(T, bool)>> = approximate_heap_2_min::<CORRUPT_EVERY_N, T>(ops)
+            .into_iter()
+            .filter_map(|op| match op {
+                Operation::Insert((x, Judgement::Survivor)) => {
+                    result.push(x);
+                    None
+                }
+                Operation::Insert((x, judgement)) => {
+                    Some(Operation::Insert((x, judgement == Judgement::Deleted)))
+                }
+                Operation::DeleteMin => Some(Operation::DeleteMin),
+                Operation::DeleteMax => Some(Operation::DeleteMax),
+            })
+            .collect();
+
+        // Removing definitely deleted items is the dual of removing definite survivors.
+        ops = undualise_ops(
+            dualise_ops(flagged)
+                .into_iter()
+                .filter(|op| !matches!(op, Operation::Insert(Reverse((_, true)))))
+                .collect(),
+        )
+        .into_iter()
+        .map(|op| op.map(|(x, _)| x))
+        .collect();
+
+        assert!(count_inserts(&ops) <= inserts / 2);
+        debug_assert!(count_deletes(&ops) <= count_inserts(&ops));
+    }
+    result
+}
+
+/// Judges every insert as a guaranteed survivor, a guaranteed deletion, or uncertain.
+///
+/// `CHUNKS` is the soft heap's `corrupt_every_n`.
+///
+/// Survivors come from the primal soft heap pass, just like in [`approximate_heap_oracle`].
+/// Deletions come from running the same pass on the dual: survivors of the dual are exactly the
+/// items that get deleted in the primal.
+///
+/// Note that an item popped by the primal soft heap without corruption is _not_ necessarily
+/// deleted: a corrupted smaller item can stay behind in its stead, and the precise heap would
+/// delete that one instead.
+///
+/// Max-deleting sequences get reversed with [`reverse_ops`] first, and reversed back afterwards.
+pub fn approximate_heap_2<const CHUNKS: usize, T: Ord + Debug>(
+    ops: Vec<Operation<T>>,
+) -> Result<Vec<Operation<(T, Judgement)>>, MixedDeletes> {
+    Ok(if deletes_max(&ops)? {
+        approximate_heap_2_min::<CHUNKS, _>(reverse_ops(ops))
+            .into_iter()
+            .map(|op| op.map(|(Reverse(x), judgement)| (x, judgement)).flip())
+            .collect()
+    } else {
+        approximate_heap_2_min::<CHUNKS, _>(ops)
+    })
+}
+
+fn approximate_heap_2_min<const CHUNKS: usize, T: Ord + Debug>(
+    ops: Vec<Operation<T>>,
 ) -> Vec<Operation<(T, Judgement)>> {
-    todo!()
+    // The dual pass sees the inserts in reverse order, so we can line its judgements up with
+    // the primal inserts by position alone, without comparing any keys.
+    let mut dual_judgements: Vec<Judgement> = approximate_heap_oracle_min(
+        dualise_ops(ops.iter().map(Operation::as_ref).collect()),
+        CHUNKS,
+    )
+    .into_iter()
+    .filter_map(|op| match op {
+        Operation::Insert((_, judgement)) => Some(judgement),
+        Operation::DeleteMin | Operation::DeleteMax => None,
+    })
+    .collect();
+
+    let judged = approximate_heap_oracle_min(ops, CHUNKS)
+        .into_iter()
+        .map(|op| {
+            op.map(|(x, judgement)| match (judgement, dual_judgements.pop()) {
+                (Judgement::Survivor, _) => (x, Judgement::Survivor),
+                (_, Some(Judgement::Survivor)) => (x, Judgement::Deleted),
+                _ => (x, Judgement::Uncertain),
+            })
+        })
+        .collect();
+    debug_assert!(dual_judgements.is_empty());
+    judged
 }
 
-#[must_use]
-pub fn approximate_heap_oracle<T: Ord + Debug + Clone>(
+/// Judges every insert as a guaranteed survivor or uncertain, with one soft heap pass.
+///
+/// Max-deleting sequences get reversed with [`reverse_ops`] first, and reversed back afterwards.
+pub fn approximate_heap_oracle<T: Ord + Debug>(
+    ops: Vec<Operation<T>>,
+    corrupt_every_n: usize,
+) -> Result<Vec<Operation<(T, Judgement)>>, MixedDeletes> {
+    Ok(if deletes_max(&ops)? {
+        approximate_heap_oracle_min(reverse_ops(ops), corrupt_every_n)
+            .into_iter()
+            .map(|op| op.map(|(Reverse(x), judgement)| (x, judgement)).flip())
+            .collect()
+    } else {
+        approximate_heap_oracle_min(ops, corrupt_every_n)
+    })
+}
+
+fn approximate_heap_oracle_min<T: Ord + Debug>(
     ops: Vec<Operation<T>>,
     corrupt_every_n: usize,
 ) -> Vec<Operation<(T, Judgement)>> {
-    // Preliminary mark all as potential survivors.
-    // Later, we will unmark some as uncertain.
+    // Preliminary mark all as uncertain.
+    // Later, we will mark the guaranteed survivors.
     let mut wrapped_ops: Vec<Operation<(T, Judgement)>> = ops
         .into_iter()
-        .map(|op| op.map(|x| (x, Judgement::Survivor)))
+        .map(|op| op.map(|x| (x, Judgement::Uncertain)))
         .collect();
 
-    // Run the actual heap operations:
-    let _: SoftHeap<&mut (T, Judgement)> =
+    // Run the actual heap operations, ordered by item alone:
+    let heap: KeyValueHeap<&T, &mut Judgement> =
         wrapped_ops
             .iter_mut()
             .fold(SoftHeap::new(corrupt_every_n), |heap, op| match op {
-                Operation::Insert(x) => heap.insert(x),
-                Operation::DeleteMin => {
-                    let (new_heap, popped_item, corrupted) = heap.pop_min();
-                    for (_item, judgement) in chain!(popped_item, corrupted) {
-                        *judgement = Judgement::Uncertain;
-                    }
-                    new_heap
-                }
+                Operation::Insert((x, judgement)) => heap.insert(Entry::new(&*x, judgement)),
+                Operation::DeleteMin => heap.pop_min().0,
+                Operation::DeleteMax => unreachable!("only called on min-deleting sequences"),
             });
-    wrapped_ops
-
-    // // Use the heap to collect guaranteed survivors from the sequence of operations,
-    // // and leave tombstones in their stead.
-    // let guaranteed_survivors: Vec<T> = Vec::from(heap)
-    //     .into_iter()
-    //     .filter_map(Option::take)
-    //     .collect();
-    // // Clean up the tombstones to get a clean vector of left-over operations:
-    // let left_over_ops: Vec<Operation<T>> = wrapped_ops.into_iter().filter_map(sequence).collect();
 
-    // (left_over_ops, guaranteed_survivors)
+    // Only the uncorrupted items left in the heap are guaranteed survivors.  Corrupted items
+    // might still be waiting in the heap to be witnessed, so we can't go by what got popped.
+    for Entry {
+        value: judgement, ..
+    } in Vec::from(heap)
+    {
+        *judgement = Judgement::Survivor;
+    }
+    wrapped_ops
 }
 
 /// Approximates the heap operations in linear time using a soft heap
@@ -266,11 +712,31 @@ pub fn approximate_heap_oracle<T: Ord + Debug + Clone>(
 ///    guaranteed_survivors := n - k - corrupted
 ///    guaranteed_survivors >= n * (1-epsilon) - k
 /// ```
-/// where epsilon is a function of CHUNKS. For CHUNKS=8, epsilon <= 1/6.
+/// where epsilon is [`estimated_epsilon`](crate::pairing::estimated_epsilon) of `corrupt_every_n`.
 ///
 /// If you can get k <= n/2, then you can get `guaranteed_survivors` >= n * (1 - 1/6) - n/2 = n/3
-#[must_use]
-pub fn approximate_heap<T: Ord + Debug + Clone>(
+///
+/// Max-deleting sequences get reversed with [`reverse_ops`] first, and reversed back afterwards.
+pub fn approximate_heap<T: Ord + Debug>(
+    ops: Vec<Operation<T>>,
+    corrupt_every_n: usize,
+) -> Result<(Vec<Operation<T>>, Vec<T>), MixedDeletes> {
+    Ok(if deletes_max(&ops)? {
+        let (left_over_ops, guaranteed_survivors) =
+            approximate_heap_min(reverse_ops(ops), corrupt_every_n);
+        (
+            unreverse_ops(left_over_ops),
+            guaranteed_survivors
+                .into_iter()
+                .map(|Reverse(x)| x)
+                .collect(),
+        )
+    } else {
+        approximate_heap_min(ops, corrupt_every_n)
+    })
+}
+
+fn approximate_heap_min<T: Ord + Debug>(
     ops: Vec<Operation<T>>,
     corrupt_every_n: usize,
 ) -> (Vec<Operation<T>>, Vec<T>) {
@@ -285,6 +751,7 @@ pub fn approximate_heap<T: Ord + Debug + Clone>(
             .fold(SoftHeap::new(corrupt_every_n), |heap, op| match op {
                 Operation::Insert(x) => heap.insert(x),
                 Operation::DeleteMin => heap.pop_min().0,
+                Operation::DeleteMax => unreachable!("only called on min-deleting sequences"),
             });
 
     // Use the heap to collect guaranteed survivors from the sequence of operations,
@@ -303,9 +770,10 @@ pub fn approximate_heap<T: Ord + Debug + Clone>(
 mod tests {
 
     use super::*;
+    use crate::pairing::LateHeap;
     use itertools::{chain, izip, Itertools};
     use proptest::prelude::{any, Strategy};
-    use proptest::prelude::{prop_assert_eq, proptest};
+    use proptest::prelude::{prop_assert, prop_assert_eq, proptest};
     use std::cmp::min;
     use std::collections::{BTreeSet, BinaryHeap};
     use std::iter::repeat_n;
@@ -318,6 +786,7 @@ mod tests {
                 match op {
                     Operation::Insert(x) => write!(f, "{x} ")?,
                     Operation::DeleteMin => write!(f, "_ ")?,
+                    Operation::DeleteMax => write!(f, "^ ")?,
                 }
             }
             Ok(())
@@ -354,6 +823,7 @@ mod tests {
                     match op {
                         Operation::Insert(_) => Operation::Insert(o),
                         Operation::DeleteMin => Operation::DeleteMin,
+                        Operation::DeleteMax => Operation::DeleteMax,
                     },
                 )
             })
@@ -374,6 +844,15 @@ mod tests {
         proptest::collection::vec(operation(), 0..100_000).prop_map(compress_operations)
     }
 
+    /// Operations with lots of equal items.
+    pub fn operations_with_ties() -> impl Strategy<Value = Vec<Operation<u32>>> {
+        proptest::collection::vec(proptest::option::of(0..10_u32), 0..10_000).prop_map(|ops| {
+            ops.into_iter()
+                .map(|x| x.map_or(Operation::DeleteMin, Operation::Insert))
+                .collect()
+        })
+    }
+
     #[must_use]
     pub fn sim_naive<T: Ord>(ops: Vec<Operation<T>>) -> Vec<T> {
         let mut h = BinaryHeap::new();
@@ -386,23 +865,37 @@ mod tests {
                 Operation::DeleteMin => {
                     h.pop();
                 }
+                Operation::DeleteMax => panic!("use sim_naive_max for max-deleting sequences"),
             }
         }
         h.into_iter().map(|Reverse(x)| x).collect::<Vec<_>>()
     }
 
     #[must_use]
-    pub fn simulate_dualised<T: Ord + std::fmt::Debug + Clone>(ops: Vec<Operation<T>>) -> Vec<T> {
-        // only works for all ops being different, ie uniquelified.
-        // We can fix that later.
+    pub fn sim_naive_max<T: Ord>(ops: Vec<Operation<T>>) -> Vec<T> {
+        let mut h = BinaryHeap::new();
+        for op in ops {
+            match op {
+                Operation::Insert(x) => h.push(x),
+                Operation::DeleteMax => {
+                    h.pop();
+                }
+                Operation::DeleteMin => panic!("use sim_naive for min-deleting sequences"),
+            }
+        }
+        h.into_vec()
+    }
 
-        let original_ops = ops.clone();
-        let ops = dualise_ops(ops);
+    #[must_use]
+    pub fn simulate_dualised<T: Ord + std::fmt::Debug + Clone>(ops: Vec<Operation<T>>) -> Vec<T> {
+        // Tagging makes equal items distinct, so we can tell which ones the dual deleted.
+        let original_ops = tag_ops(ops);
+        let ops = dualise_ops(original_ops.clone());
 
         let result = sim_naive(ops);
         let result = result
             .into_iter()
-            .map(|Reverse(x)| x)
+            .map(|Reverse((_, index))| index)
             .collect::<BTreeSet<_>>();
 
         // You can do this one via indices and direct lookups, so you don't have to compare keys.
@@ -410,7 +903,7 @@ mod tests {
         original_ops
             .into_iter()
             .filter_map(|op| match op {
-                Operation::Insert(x) if !result.contains(&x) => Some(x),
+                Operation::Insert((x, index)) if !result.contains(&index) => Some(x),
                 _ => None,
             })
             .collect()
@@ -430,15 +923,8 @@ mod tests {
     pub fn simulate_pairing_debug<T: Ord + std::fmt::Debug + Clone>(
         ops: Vec<Operation<T>>,
     ) -> Vec<T> {
-        // TODO: this looks suspicious.  Corrupting every first (or even every second) element shouldn't corrupt much more in total?
-
-        // CHUNKS>=8 and EPS = 6 seem to work.
-        // Chunks>=6 and EPS=3 also seem to work.
-        // Hmm, 0 shouldn't work, but it does?
         const EPS: usize = 4;
-        // Should really be +1 (?), I think, but we want some slack.
-        const CORRUPT_EVERY_N: usize = EPS + 3;
-        let mut pairing: SoftHeap<T> = SoftHeap::new(CORRUPT_EVERY_N);
+        let mut pairing: SoftHeap<T> = SoftHeap::with_epsilon(0.25);
         let mut inserts_so_far = 0;
         for op in ops {
             pairing = match op {
@@ -447,6 +933,7 @@ mod tests {
                     pairing.insert(x)
                 }
                 Operation::DeleteMin => pairing.pop_min().0,
+                Operation::DeleteMax => panic!("soft heaps only support DeleteMin"),
             };
             let un = pairing.count_uncorrupted();
             let co = pairing.count_corrupted();
@@ -471,6 +958,48 @@ mod tests {
         Vec::from(pairing)
     }
 
+    /// Like [`simulate_pairing_debug`], but for [`LateHeap`].
+    ///
+    /// Also checks that every inserted item gets reported exactly once, either popped or
+    /// witnessed, after we drain the heap at the end.
+    pub fn simulate_late_debug<T: Ord + std::fmt::Debug + Clone>(ops: Vec<Operation<T>>) {
+        const EPS: usize = 4;
+        let mut heap: LateHeap<T> = LateHeap::default();
+        let mut inserted = vec![];
+        let mut reported = vec![];
+        for op in ops {
+            heap = match op {
+                Operation::Insert(x) => {
+                    inserted.push(x.clone());
+                    heap.insert(x)
+                }
+                Operation::DeleteMin => {
+                    let (heap, item, witnessed) = heap.pop_min();
+                    reported.extend(item);
+                    reported.extend(witnessed);
+                    heap
+                }
+                Operation::DeleteMax => panic!("soft heaps only support DeleteMin"),
+            };
+            let co = heap.count_corrupted();
+            assert!(
+                EPS * co <= inserted.len(),
+                "{EPS} * {co} == {} !<= {}",
+                EPS * co,
+                inserted.len(),
+            );
+        }
+        while !heap.is_empty() {
+            let (rest, item, witnessed) = heap.heavy_pop_min();
+            reported.extend(item);
+            reported.extend(witnessed);
+            heap = rest;
+        }
+        inserted.sort();
+        reported.sort();
+        assert_eq!(inserted, reported);
+    }
+
     #[test]
     fn test_enough_corruption() {
         let n = 1000;
@@ -510,6 +1039,74 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_bounded_corruption_epsilon() {
+        let n = 1000;
+        let mut pairing = SoftHeap::with_epsilon(0.125).guarded();
+        for i in 0..n {
+            pairing = pairing.insert(i);
+        }
+
+        while pairing.count_uncorrupted() > 0 {
+            assert_eq!(pairing.check_budget(), Ok(()));
+
+            let (new_pairing, _, _) = pairing.pop_min();
+            pairing = new_pairing;
+        }
+    }
+
+    #[test]
+    fn test_mixed_deletes() {
+        let ops = vec![
+            Operation::Insert(1),
+            Operation::Insert(2),
+            Operation::DeleteMin,
+            Operation::DeleteMax,
+        ];
+        assert_eq!(deletes_max(&ops), Err(MixedDeletes));
+        assert_eq!(approximate_heap(ops.clone(), 8), Err(MixedDeletes));
+        assert_eq!(approximate_heap_oracle(ops.clone(), 8), Err(MixedDeletes));
+        assert_eq!(approximate_heap_2::<8, _>(ops.clone()), Err(MixedDeletes));
+        assert_eq!(
+            try_linear_loop(ops, OnStall::Exact),
+            Err(LoopError::MixedDeletes(MixedDeletes))
+        );
+    }
+
+    #[test]
+    fn test_linear_loop_without_clone() {
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+        struct Item(u32);
+
+        let ops: Vec<_> = (0..1_000)
+            .map(|i| Operation::Insert(Item(i)))
+            .chain((0..700).map(|_| Operation::DeleteMin))
+            .collect();
+        let mut via_loop = try_linear_loop(ops, OnStall::Exact).unwrap();
+        via_loop.sort_unstable();
+        assert_eq!(via_loop, (700..1_000).map(Item).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_try_linear_loop_stalls() {
+        let ops: Vec<_> = (0..1_000_u32)
+            .map(Operation::Insert)
+            .chain(repeat_n(Operation::DeleteMin, 400))
+            .collect();
+        let Err(LoopError::Stalled(stalled)) = linear_loop_min_with(2, ops.clone(), OnStall::Fail)
+        else {
+            panic!("corrupting after every pair of children should stall");
+        };
+        assert_eq!(stalled.iteration, 0);
+        assert_eq!(stalled.inserts, 1_000);
+        assert_eq!(stalled.deletes, 400);
+        assert!(stalled.remaining > 1_000 / 6 + 400, "{stalled}");
+
+        let mut via_loop = try_linear_loop(ops, OnStall::Fail).unwrap();
+        via_loop.sort_unstable();
+        assert_eq!(via_loop, (400..1_000).collect::<Vec<_>>());
+    }
+
     proptest! {
         #[test]
         fn corruption_simple(ops in operations()) {
@@ -521,6 +1118,16 @@ mod tests {
             let _ = simulate_pairing_debug(ops.0);
         }
 
+        #[test]
+        fn late_corruption_simple(ops in operations()) {
+            simulate_late_debug(ops);
+        }
+
+        #[test]
+        fn late_corruption(ops in full_ops(10_000)) {
+            simulate_late_debug(ops.0);
+        }
+
         #[test]
         fn test_simulate_normalised_ops(ops in operations()) {
             let mut naive = sim_naive(ops.clone());
@@ -554,5 +1161,143 @@ mod tests {
 
             prop_assert_eq!(&naive, &pairing_in_2);
         }
+
+        #[test]
+        fn test_simulate_dualised_with_ties(ops in operations_with_ties()) {
+            let mut naive = sim_naive(ops.clone());
+            let mut dualised = simulate_dualised(ops);
+
+            naive.sort_unstable();
+            dualised.sort_unstable();
+
+            prop_assert_eq!(naive, dualised);
+        }
+
+        #[test]
+        fn test_linear_loop_with_ties(ops in operations_with_ties()) {
+            let mut naive = sim_naive(ops.clone());
+            let mut via_loop = linear_loop(ops.clone());
+            let mut via_loop_2 = linear_loop_2(ops.clone());
+            let mut via_max: Vec<u32> = linear_loop(reverse_ops(ops))
+                .into_iter()
+                .map(|Reverse(x)| x)
+                .collect();
+
+            naive.sort_unstable();
+            via_loop.sort_unstable();
+            via_loop_2.sort_unstable();
+            via_max.sort_unstable();
+
+            prop_assert_eq!(&naive, &via_loop);
+            prop_assert_eq!(&naive, &via_loop_2);
+            prop_assert_eq!(naive, via_max);
+        }
+
+        #[test]
+        fn test_try_linear_loop_falls_back(ops in operations_with_ties()) {
+            let mut naive = sim_naive(ops.clone());
+            // Corrupting after every pair of children is hopeless.
+            let mut via_loop: Vec<u32> =
+                linear_loop_min_with(2, tag_ops(ops), OnStall::Exact)
+                    .unwrap()
+                    .into_iter()
+                    .map(|(x, _)| x)
+                    .collect();
+            naive.sort_unstable();
+            via_loop.sort_unstable();
+            prop_assert_eq!(naive, via_loop);
+        }
+
+        #[test]
+        fn test_delete_max(ops in full_ops(10_000)) {
+            let ops: Vec<_> = ops.0.into_iter().map(Operation::flip).collect();
+            let mut naive = sim_naive_max(ops.clone());
+            let mut normalised = sim_naive_max(normalise_ops(ops.clone()));
+            let mut via_loop = linear_loop(ops.clone());
+            let mut via_loop_2 = linear_loop_2(ops);
+
+            naive.sort_unstable();
+            normalised.sort_unstable();
+            via_loop.sort_unstable();
+            via_loop_2.sort_unstable();
+
+            prop_assert_eq!(&naive, &normalised);
+            prop_assert_eq!(&naive, &via_loop);
+            prop_assert_eq!(&naive, &via_loop_2);
+        }
+
+        #[test]
+        fn test_approximate_heap_max(ops in full_ops(10_000)) {
+            let ops: Vec<_> = ops.0.into_iter().map(Operation::flip).collect();
+            let mut naive = sim_naive_max(ops.clone());
+            let (left_over_ops, guaranteed_survivors) = approximate_heap(ops.clone(), 8).unwrap();
+            let mut combined = sim_naive_max(left_over_ops);
+            combined.extend(guaranteed_survivors);
+            let survivors: BTreeSet<_> = naive.iter().copied().collect();
+            for op in approximate_heap_2::<8, _>(ops.clone()).unwrap() {
+                match op {
+                    Operation::Insert((x, Judgement::Survivor)) => prop_assert!(survivors.contains(&x)),
+                    Operation::Insert((x, Judgement::Deleted)) => prop_assert!(!survivors.contains(&x)),
+                    Operation::DeleteMin => prop_assert!(false, "deletes keep their direction"),
+                    _ => {}
+                }
+            }
+            let mut dualised: Vec<_> = sim_naive_max(dualise_ops(ops))
+                .into_iter()
+                .map(|Reverse(x)| x)
+                .collect();
+            dualised.extend(&naive);
+
+            naive.sort_unstable();
+            combined.sort_unstable();
+            dualised.sort_unstable();
+            prop_assert_eq!(&naive, &combined);
+            // Every item either survives the primal or the dual, but not both.
+            prop_assert!(dualised.windows(2).all(|w| w[0] < w[1]));
+        }
+
+        #[test]
+        fn test_dual_ops(ops in operations()) {
+            let inserts = count_inserts(&ops);
+            let dual = dual_ops(ops.clone());
+            prop_assert!(deletes_max(&dual) == Ok(true) || count_deletes(&dual) == 0);
+
+            let survivors: BTreeSet<_> = sim_naive(ops).into_iter().collect();
+            let dual_survivors: BTreeSet<_> = sim_naive_max(dual.clone()).into_iter().collect();
+            prop_assert!(survivors.is_disjoint(&dual_survivors));
+            prop_assert_eq!(survivors.len() + dual_survivors.len(), inserts);
+
+            // Without any deletes, there's no telling which direction a sequence has.
+            let again = dual_ops(dual);
+            let mut again = if deletes_max(&again) == Ok(true) { sim_naive_max(again) } else { sim_naive(again) };
+            again.sort_unstable();
+            prop_assert_eq!(again, survivors.into_iter().collect::<Vec<_>>());
+        }
+
+        #[test]
+        fn test_approximate_heap_2(ops in full_ops(10_000)) {
+            let survivors = sim_naive(ops.0.clone()).into_iter().collect::<BTreeSet<_>>();
+            for op in approximate_heap_2::<8, _>(ops.0).unwrap() {
+                match op {
+                    Operation::Insert((x, Judgement::Survivor)) => prop_assert!(survivors.contains(&x)),
+                    Operation::Insert((x, Judgement::Deleted)) => prop_assert!(!survivors.contains(&x)),
+                    _ => {}
+                }
+            }
+        }
+
+        #[test]
+        fn test_linear_loop_2(ops in full_ops(10_000)) {
+            let mut naive = sim_naive(ops.0.clone());
+            let mut via_loop = linear_loop(ops.0.clone());
+            let mut via_loop_2 = linear_loop_2(ops.0);
+
+            naive.sort_unstable();
+            via_loop.sort_unstable();
+            via_loop_2.sort_unstable();
+
+            prop_assert_eq!(&via_loop, &via_loop_2);
+            prop_assert_eq!(&naive, &via_loop_2);
+        }
     }
 }
@@ -0,0 +1,521 @@
+// Selection with a linear number of comparisons, via soft heaps.
+//
+// Like in 'Selection from heaps, row-sorted matrices and X+Y using soft heaps' by Kaplan et al,
+// https://arxiv.org/abs/1802.07041 : put all items into a soft heap, and pop about a third of
+// them.  The last item we pop has a rank between a third and two thirds, give or take the
+// corruption.  Partition around it, and keep going on the side that has the item we want.
+//
+// Unlike `select_nth_unstable`, that's linear in the worst case, and not just in expectation.
+
+use std::cmp::Ordering;
+use std::ops::Add;
+
+use crate::entry::Entry;
+use crate::pairing::{Pairing, SoftHeap};
+
+/// Error rate of the soft heap that finds our pivots.
+const EPSILON: f64 = 1.0 / 8.0;
+
+/// Below this size, we just sort.
+const SMALL: usize = 16;
+
+/// Returns the `k`th smallest item, counting from zero.
+///
+/// Uses a linear number of comparisons.  With ties, returns one of the equal items.
+///
+/// # Panics
+///
+/// Panics if `k >= items.len()`.
+#[must_use]
+pub fn select_kth<T: Ord>(items: Vec<T>, k: usize) -> T {
+    assert!(
+        k < items.len(),
+        "k = {k} is out of bounds for {} items",
+        items.len()
+    );
+    let (_, rest) = split(items, k);
+    rest.into_iter()
+        .next()
+        .expect("split leaves the kth item first")
+}
+
+/// Returns the `k` smallest items, in no particular order.
+///
+/// Uses a linear number of comparisons.  Returns everything, if there are fewer than `k` items.
+#[must_use]
+pub fn top_k<T: Ord>(items: Vec<T>, k: usize) -> Vec<T> {
+    split(items, k).0
+}
+
+/// Splits `items` into the `k` smallest ones, and the rest, with the `k`th smallest item first.
+fn split<T: Ord>(mut items: Vec<T>, mut k: usize) -> (Vec<T>, Vec<T>) {
+    // Invariant: everything in `low` <= everything in `items` <= everything in `high`, and we
+    // still need the `k` smallest of `items`.
+    let mut low = vec![];
+    let mut high = vec![];
+    loop {
+        if items.len() <= SMALL {
+            items.sort_unstable();
+            let mut rest = items.split_off(k.min(items.len()));
+            low.append(&mut items);
+            rest.append(&mut high);
+            return (low, rest);
+        }
+        let pivot = items.swap_remove(pivot(&items));
+        let (mut less, mut equal, mut greater) = (vec![], vec![], vec![]);
+        for item in items {
+            match item.cmp(&pivot) {
+                Ordering::Less => less.push(item),
+                Ordering::Equal => equal.push(item),
+                Ordering::Greater => greater.push(item),
+            }
+        }
+        equal.push(pivot);
+        if k < less.len() {
+            high.append(&mut equal);
+            high.append(&mut greater);
+            items = less;
+        } else if k < less.len() + equal.len() {
+            let mut rest = equal.split_off(k - less.len());
+            low.append(&mut less);
+            low.append(&mut equal);
+            rest.append(&mut greater);
+            rest.append(&mut high);
+            return (low, rest);
+        } else {
+            k -= less.len() + equal.len();
+            low.append(&mut less);
+            low.append(&mut equal);
+            items = greater;
+        }
+    }
+}
+
+/// Index of an item with rank between about a third and two thirds of `items`.
+///
+/// Every heavy pop removes the root item together with its pool.  Pooled items are at most as
+/// big as the root item, so once a third of the items are gone, the last root item is at least
+/// as big as a third of the items.  Only corrupted items still in the heap can be smaller than
+/// it without having been popped, and there are at most `EPSILON * n` of those.
+fn pivot<T: Ord>(items: &[T]) -> usize {
+    let n = items.len();
+    let mut heap = SoftHeap::with_epsilon(EPSILON);
+    heap.extend(items.iter().zip(0..));
+    loop {
+        let (item, _witnessed) = heap.heavy_pop();
+        let (_, index) = item.expect("We stop before the heap runs out.");
+        if 3 * heap.size <= 2 * n {
+            return index;
+        }
+    }
+}
+
+/// Returns the `k` smallest keys of a heap-ordered tree, in no particular order.
+///
+/// Doesn't change the tree.  Pooled corrupted items don't count, only the key of each node.
+///
+/// Uses `O(k + d)` comparisons, where `d` is the number of children of the nodes we return.  We
+/// can't do better: any of those children could be among the `k` smallest.  So for trees of
+/// bounded degree, that's `O(k)`.
+#[must_use]
+pub fn top_k_of_tree<T: Ord>(tree: &Pairing<T>, k: usize) -> Vec<&T> {
+    let candidates = candidates(
+        vec![tree],
+        k,
+        |node| &node.key.item,
+        |node| node.children.iter(),
+    );
+    top_k(candidates, k)
+        .into_iter()
+        .map(|Entry { key, .. }| key)
+        .collect()
+}
+
+/// Returns the `k`th smallest of all sums `x + y`, counting from zero.
+///
+/// Takes `O(n + k)` time, where `n` is the length of `xs` and `ys`, instead of materialising all
+/// the `n^2` sums.  Heapifying both vectors gives us an implicit heap-ordered tree of all sums
+/// with degree at most four, that we can select from like in [`top_k_of_tree`].
+///
+/// # Panics
+///
+/// Panics if `k` is not less than the number of sums.
+#[must_use]
+pub fn select_kth_sum<T: Ord + Add<Output = T> + Clone>(xs: &[T], ys: &[T], k: usize) -> T {
+    assert!(
+        k < xs.len() * ys.len(),
+        "k = {k} is out of bounds for {} sums",
+        xs.len() * ys.len()
+    );
+    let (mut xs, mut ys) = (xs.to_vec(), ys.to_vec());
+    heapify(&mut xs);
+    heapify(&mut ys);
+    // `(i, 0)` has children `(2i + 1, 0)`, `(2i + 2, 0)`, `(i, 1)` and `(i, 2)`.  `(i, j)` with
+    // `j > 0` has children `(i, 2j + 1)` and `(i, 2j + 2)`.  That reaches every pair exactly once.
+    let (nx, ny) = (xs.len(), ys.len());
+    let children = |(i, j): (usize, usize)| {
+        let children = if j == 0 {
+            [(2 * i + 1, 0), (2 * i + 2, 0), (i, 1), (i, 2)]
+        } else {
+            [
+                (i, 2 * j + 1),
+                (i, 2 * j + 2),
+                (i, usize::MAX),
+                (i, usize::MAX),
+            ]
+        };
+        children.into_iter().filter(move |&(i, j)| i < nx && j < ny)
+    };
+    let candidates = candidates(
+        vec![(0, 0)],
+        k + 1,
+        |(i, j)| xs[i].clone() + ys[j].clone(),
+        children,
+    );
+    select_kth(candidates, k).key
+}
+
+/// Returns the `k`th smallest entry of a matrix whose rows are sorted, counting from zero.
+///
+/// Rows can have different lengths.  Takes `O(n + k)` time, where `n` is the number of rows: each
+/// row is a path in an implicit heap-ordered forest.
+///
+/// # Panics
+///
+/// Panics if `k` is not less than the number of entries.
+#[must_use]
+pub fn select_kth_in_rows<T: Ord, R: AsRef<[T]>>(rows: &[R], k: usize) -> &T {
+    let rows: Vec<&[T]> = rows.iter().map(AsRef::as_ref).collect();
+    let entries: usize = rows.iter().map(|row| row.len()).sum();
+    assert!(
+        k < entries,
+        "k = {k} is out of bounds for {entries} entries"
+    );
+    let roots = (0..rows.len())
+        .filter(|&i| !rows[i].is_empty())
+        .map(|i| (i, 0))
+        .collect();
+    let candidates = candidates(
+        roots,
+        k + 1,
+        |(i, j)| &rows[i][j],
+        |(i, j)| (j + 1 < rows[i].len()).then_some((i, j + 1)),
+    );
+    select_kth(candidates, k).key
+}
+
+/// Collects nodes of an implicit heap-ordered forest, that include the `k` smallest.  That's at
+/// least `k` nodes, or all of them, but not many more.
+///
+/// Works in two phases.  First, we explore the forest with a soft heap, until `k` nodes have
+/// left the soft heap.  Those nodes and their pools are all at most the largest key we popped,
+/// so that's a threshold with at least `k` nodes at or below it.  Second, we collect all nodes
+/// strictly below the threshold, and as many that tie with it as we need to make up `k`.
+/// Corruption only makes the first phase look at a few more nodes, but never makes us miss any.
+fn candidates<N: Copy, K: Ord, I: IntoIterator<Item = N>>(
+    roots: Vec<N>,
+    k: usize,
+    key: impl Fn(N) -> K,
+    children: impl Fn(N) -> I,
+) -> Vec<Entry<K, N>> {
+    if k == 0 {
+        return vec![];
+    }
+    let keyed = |node| Entry::new(key(node), node);
+
+    let mut heap = SoftHeap::with_epsilon(EPSILON);
+    heap.extend(roots.iter().copied().map(keyed));
+    let mut threshold: Option<K> = None;
+    while heap.inserted - heap.size < k {
+        let (item, witnessed) = heap.heavy_pop();
+        let Some(item) = item else {
+            threshold = None;
+            break;
+        };
+        let node = item.value;
+        threshold = Some(match threshold {
+            None => item.key,
+            Some(t) => t.max(item.key),
+        });
+        // Corrupted nodes still in the soft heap might be small, so we explore below them, too.
+        for node in std::iter::once(node).chain(witnessed.into_iter().map(|w| w.value)) {
+            heap.extend(children(node).into_iter().map(keyed));
+        }
+    }
+
+    let mut result = vec![];
+    let mut ties = vec![];
+    let mut todo = roots;
+    while let Some(node) = todo.pop() {
+        let item = keyed(node);
+        match threshold.as_ref().map_or(Ordering::Less, |t| item.key.cmp(t)) {
+            Ordering::Less => {
+                todo.extend(children(node));
+                result.push(item);
+            }
+            Ordering::Equal => ties.push(item),
+            Ordering::Greater => {}
+        }
+    }
+    // Below a tie, there are only more ties and bigger keys.  With lots of duplicates, there
+    // could be far more than `k` ties, so we only take as many as we still need.
+    while result.len() < k {
+        let Some(item) = ties.pop() else {
+            break;
+        };
+        for child in children(item.value) {
+            let child = keyed(child);
+            if threshold.as_ref().is_some_and(|t| child.key == *t) {
+                ties.push(child);
+            }
+        }
+        result.push(item);
+    }
+    result
+}
+
+/// Heapifies `items` into an implicit binary min-heap, with a linear number of comparisons.
+fn heapify<T: Ord>(items: &mut [T]) {
+    for start in (0..items.len() / 2).rev() {
+        let mut i = start;
+        loop {
+            let smallest = [2 * i + 1, 2 * i + 2]
+                .into_iter()
+                .filter(|&c| c < items.len())
+                .fold(i, |s, c| if items[c] < items[s] { c } else { s });
+            if smallest == i {
+                break;
+            }
+            items.swap(i, smallest);
+            i = smallest;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::{with_counter, Counted};
+    use proptest::prelude::{prop_assert_eq, prop_assume, proptest};
+    use rand::seq::SliceRandom;
+    use rand::Rng;
+
+    #[test]
+    fn linear_comparisons() {
+        const N: usize = 100_000;
+        let mut items: Vec<u32> = (0..u32::try_from(N).unwrap()).collect();
+        items.shuffle(&mut rand::rng());
+
+        for k in [0, N / 3, N / 2, N - 1] {
+            let (counter, wrapped) = with_counter(items.clone());
+            assert_eq!(select_kth(wrapped, k).into_inner() as usize, k);
+            assert!(counter.get() <= 25 * N, "{k}: {}", counter.get());
+        }
+
+        let (counter, wrapped) = with_counter(items.clone());
+        let mut top: Vec<u32> = top_k(wrapped, 1_000)
+            .into_iter()
+            .map(Counted::into_inner)
+            .collect();
+        assert!(counter.get() <= 25 * N, "{}", counter.get());
+        top.sort_unstable();
+        assert_eq!(top, (0..1_000).collect::<Vec<_>>());
+
+        // Lots of duplicates, in a flat list and in a tree.
+        for distinct in [1, 10] {
+            let duplicates: Vec<u32> = items.iter().map(|x| x % distinct).collect();
+            let mut sorted = duplicates.clone();
+            sorted.sort_unstable();
+            for k in [0, N / 3, N - 1] {
+                let (counter, wrapped) = with_counter(duplicates.clone());
+                assert_eq!(select_kth(wrapped, k).into_inner(), sorted[k]);
+                assert!(counter.get() <= 25 * N, "{distinct} {k}: {}", counter.get());
+            }
+
+            let (counter, wrapped) = with_counter(duplicates);
+            let mut heap = SoftHeap::new(usize::MAX);
+            heap.extend(wrapped);
+            heap.pop();
+            let tree = heap.root.unwrap();
+            for k in [1, 100, 1_000] {
+                counter.set(0);
+                let mut top: Vec<u32> = top_k_of_tree(&tree, k)
+                    .into_iter()
+                    .map(|x| *x.value())
+                    .collect();
+                // The root is always among the `k` smallest, so its children count towards `d`.
+                let bound = 100 * k + 4 * tree.children.len();
+                assert!(counter.get() <= bound, "{distinct} {k}: {}", counter.get());
+                top.sort_unstable();
+                assert_eq!(top, &sorted[1..=k]);
+            }
+        }
+    }
+
+    #[test]
+    fn all_equal() {
+        let (counter, wrapped) = with_counter(vec![7; 10_000]);
+        assert_eq!(select_kth(wrapped, 5_000).into_inner(), 7);
+        assert!(counter.get() <= 25 * 10_000, "{}", counter.get());
+    }
+
+    /// Builds a pairing heap from `items`, and pops a few to give it some shape.
+    fn tree(items: Vec<u32>, pops: usize) -> Pairing<u32> {
+        let pops = pops.min(items.len() - 1);
+        let mut heap = SoftHeap::new(usize::MAX);
+        heap.extend(items);
+        for _ in 0..pops {
+            heap.pop();
+        }
+        heap.root.unwrap()
+    }
+
+    #[test]
+    fn tree_comparisons() {
+        const N: u32 = 100_000;
+        let mut items: Vec<u32> = (0..N).collect();
+        items.shuffle(&mut rand::rng());
+        let (counter, wrapped) = with_counter(items);
+        let mut heap = SoftHeap::new(usize::MAX);
+        heap.extend(wrapped);
+        for _ in 0..1_000 {
+            heap.pop();
+        }
+        let tree = heap.root.unwrap();
+        let k = 1_000;
+
+        let degrees: usize = tree
+            .nodes()
+            .filter(|node| node.key.item.value() < &(1_000 + 1_000))
+            .map(|node| 1 + node.children.len())
+            .sum();
+        counter.set(0);
+        let mut top: Vec<u32> = top_k_of_tree(&tree, k)
+            .into_iter()
+            .map(|x| *x.value())
+            .collect();
+        assert!(counter.get() <= 20 * degrees, "{} {degrees}", counter.get());
+        top.sort_unstable();
+        assert_eq!(top, (1_000..2_000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn sums_comparisons() {
+        const N: usize = 1_000;
+        const K: usize = 20_000;
+        let mut rng = rand::rng();
+        let xs: Vec<u64> = (0..N).map(|_| rng.random_range(0..1_000_000)).collect();
+        let ys: Vec<u64> = (0..N).map(|_| rng.random_range(0..1_000_000)).collect();
+        let mut sums: Vec<u64> = xs
+            .iter()
+            .flat_map(|x| ys.iter().map(move |y| x + y))
+            .collect();
+        sums.sort_unstable();
+
+        let (counter, xs) = with_counter(xs);
+        let ys: Vec<_> = ys.into_iter().map(|y| Counted::new(y, &counter)).collect();
+        assert_eq!(*select_kth_sum(&xs, &ys, K).value(), sums[K]);
+        assert!(counter.get() <= 50 * (N + K), "{}", counter.get());
+    }
+
+    #[test]
+    fn all_equal_sums_and_rows() {
+        const N: usize = 1_000;
+        const K: usize = 20_000;
+        let (counter, xs) = with_counter(vec![3_u64; N]);
+        let ys = xs.clone();
+        assert_eq!(*select_kth_sum(&xs, &ys, K).value(), 6);
+        assert!(counter.get() <= 50 * (N + K), "{}", counter.get());
+
+        let (counter, row) = with_counter(vec![3_u64; N / 10]);
+        let rows = vec![row; N];
+        counter.set(0);
+        assert_eq!(*select_kth_in_rows(&rows, K).value(), 3);
+        assert!(counter.get() <= 50 * (N + K), "{}", counter.get());
+    }
+
+    #[test]
+    fn rows_comparisons() {
+        const N: usize = 1_000;
+        const K: usize = 20_000;
+        let mut rng = rand::rng();
+        let rows: Vec<Vec<u32>> = (0..N)
+            .map(|_| {
+                let mut row: Vec<u32> = (0..rng.random_range(0..100))
+                    .map(|_| rng.random_range(0..1_000_000))
+                    .collect();
+                row.sort_unstable();
+                row
+            })
+            .collect();
+        let mut entries: Vec<u32> = rows.iter().flatten().copied().collect();
+        entries.sort_unstable();
+        let k = K.min(entries.len() - 1);
+
+        let (counter, _) = with_counter(Vec::<u32>::new());
+        let rows: Vec<Vec<Counted<u32>>> = rows
+            .into_iter()
+            .map(|row| row.into_iter().map(|x| Counted::new(x, &counter)).collect())
+            .collect();
+        assert_eq!(*select_kth_in_rows(&rows, k).value(), entries[k]);
+        assert!(counter.get() <= 50 * (N + k), "{}", counter.get());
+    }
+
+    proptest! {
+        #[test]
+        fn sums_match_sorting(
+            xs in proptest::collection::vec(0..100_u32, 1..50),
+            ys in proptest::collection::vec(0..100_u32, 1..50),
+            k in 0..2_500_usize,
+        ) {
+            let mut sums: Vec<u32> = xs.iter().flat_map(|x| ys.iter().map(move |y| x + y)).collect();
+            sums.sort_unstable();
+            let k = k % sums.len();
+            prop_assert_eq!(select_kth_sum(&xs, &ys, k), sums[k]);
+        }
+
+        #[test]
+        fn rows_match_sorting(
+            rows in proptest::collection::vec(proptest::collection::vec(0..100_u32, 0..50), 1..50),
+            k in 0..2_500_usize,
+        ) {
+            let rows: Vec<Vec<u32>> = rows.into_iter().map(|mut row| { row.sort_unstable(); row }).collect();
+            let mut entries: Vec<u32> = rows.iter().flatten().copied().collect();
+            entries.sort_unstable();
+            prop_assume!(!entries.is_empty());
+            let k = k % entries.len();
+            prop_assert_eq!(*select_kth_in_rows(&rows, k), entries[k]);
+        }
+
+        #[test]
+        fn tree_matches_sorting(
+            items in proptest::collection::vec(0..100_u32, 1..2_000),
+            pops in 0..100_usize,
+            k in 0..2_000_usize,
+        ) {
+            let tree = tree(items, pops);
+            let mut sorted: Vec<u32> = tree.nodes().map(|node| node.key.item).collect();
+            sorted.sort_unstable();
+            let k = k % (sorted.len() + 2);
+
+            let mut top: Vec<u32> = top_k_of_tree(&tree, k).into_iter().copied().collect();
+            top.sort_unstable();
+            prop_assert_eq!(top, &sorted[..k.min(sorted.len())]);
+        }
+
+        #[test]
+        fn matches_sorting(
+            items in proptest::collection::vec(0..100_u32, 1..2_000),
+            k in 0..2_000_usize,
+        ) {
+            let mut sorted = items.clone();
+            sorted.sort_unstable();
+            let k = k % items.len();
+            prop_assert_eq!(select_kth(items.clone(), k), sorted[k]);
+
+            let mut top = top_k(items, k);
+            top.sort_unstable();
+            prop_assert_eq!(top, &sorted[..k]);
+        }
+    }
+}
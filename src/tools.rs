@@ -5,6 +5,7 @@ pub fn previous_full_multiple(n: usize, m: usize) -> usize {
 
 use std::cell::Cell;
 use std::cmp::Ordering;
+use std::ops::Add;
 use std::rc::Rc;
 
 /* ---------- counted wrapper ---------- */
@@ -16,7 +17,7 @@ pub struct Counted<T> {
 }
 
 impl<T> Counted<T> {
-    fn new(value: T, counter: &Rc<Cell<usize>>) -> Self {
+    pub fn new(value: T, counter: &Rc<Cell<usize>>) -> Self {
         Self {
             value,
             counter: counter.clone(),
@@ -26,6 +27,11 @@ impl<T> Counted<T> {
     pub fn into_inner(self) -> T {
         self.value
     }
+
+    /// Looks at the value without counting a comparison.
+    pub const fn value(&self) -> &T {
+        &self.value
+    }
 }
 
 /* --- trait implementations (unchanged in spirit) --- */
@@ -48,6 +54,23 @@ impl<T: Ord> PartialEq for Counted<T> {
 }
 impl<T: Ord> Eq for Counted<T> {}
 
+/// Adding doesn't count as a comparison.  Sums share the counter of the left operand.
+impl<T: Add<Output = T>> Add for Counted<T> {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self {
+            value: self.value + other.value,
+            counter: self.counter,
+        }
+    }
+}
+
+impl<T: Clone> Clone for Counted<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.value.clone(), &self.counter)
+    }
+}
+
 /* ---------- helper: wrap a Vec and hand the counter back ---------- */
 
 /// Consumes a vector, wraps every element, and returns
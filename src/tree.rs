@@ -0,0 +1,143 @@
+// Clone, compare, order and print trees without recursion.
+//
+// Deep heaps are long chains of nodes, and the derived implementations recurse once per level.
+// That overflows the stack long before we run out of memory.  So `Pairing` and `Witnessed`
+// only say how to handle one node at a time, and the walks here keep their own stack.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+/// A node that keeps its children in a `Vec`.
+///
+/// The walks below take care of the children, and take care of everything else in a node via
+/// a closure.
+pub(crate) trait Tree: Sized {
+    fn children(&self) -> &[Self];
+    fn children_mut(&mut self) -> &mut Vec<Self>;
+}
+
+/// `clone_node` clones a node, but without any children.
+pub(crate) fn clone_tree<N: Tree>(root: &N, clone_node: impl Fn(&N) -> N) -> N {
+    // Each copy on the stack has cloned as many children as it has so far, so its length says
+    // which child of the original comes next.
+    let mut stack = vec![(root, clone_node(root))];
+    loop {
+        let (original, copy) = stack
+            .last_mut()
+            .expect("we return when the stack runs empty");
+        if let Some(child) = original.children().get(copy.children_mut().len()) {
+            stack.push((child, clone_node(child)));
+            continue;
+        }
+        let (_, done) = stack.pop().expect("we just looked at the top");
+        match stack.last_mut() {
+            Some((_, parent)) => parent.children_mut().push(done),
+            None => return done,
+        }
+    }
+}
+
+/// `eq_node` compares two nodes, but not their children.
+pub(crate) fn eq_trees<N: Tree>(a: &[N], b: &[N], eq_node: impl Fn(&N, &N) -> bool) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut todo: Vec<(&N, &N)> = a.iter().zip(b).collect();
+    while let Some((a, b)) = todo.pop() {
+        if !eq_node(a, b) || a.children().len() != b.children().len() {
+            return false;
+        }
+        todo.extend(a.children().iter().zip(b.children()));
+    }
+    true
+}
+
+/// Compares two lists of trees lexicographically, just like the derived `PartialOrd` would.
+///
+/// `cmp_node` compares two nodes, but not their children.  A node's children come before its
+/// later siblings, like in a pre-order walk.
+pub(crate) fn cmp_trees<N: Tree>(
+    a: &[N],
+    b: &[N],
+    cmp_node: impl Fn(&N, &N) -> Option<Ordering>,
+) -> Option<Ordering> {
+    // Pairs of sibling lists, that we still have to compare.
+    let mut todo = vec![(a, b)];
+    while let Some(lists) = todo.pop() {
+        match lists {
+            ([], []) => {}
+            ([], _) => return Some(Ordering::Less),
+            (_, []) => return Some(Ordering::Greater),
+            ([a, a_rest @ ..], [b, b_rest @ ..]) => match cmp_node(a, b)? {
+                Ordering::Equal => {
+                    todo.push((a_rest, b_rest));
+                    todo.push((a.children(), b.children()));
+                }
+                unequal => return Some(unequal),
+            },
+        }
+    }
+    Some(Ordering::Equal)
+}
+
+enum Step<'a, N> {
+    Open(&'a N),
+    Text(&'static str),
+}
+
+/// Writes a tree, just like the derived `Debug` would.
+///
+/// `open` writes everything in a node that comes before its list of children, and `close`
+/// comes after.
+pub(crate) fn fmt_tree<N: Tree>(
+    root: &N,
+    f: &mut fmt::Formatter<'_>,
+    open: impl Fn(&N, &mut fmt::Formatter<'_>) -> fmt::Result,
+    close: &'static str,
+) -> fmt::Result {
+    walk(vec![Step::Open(root)], f, open, close)
+}
+
+/// Writes `[tree, tree, ..]`, like [`fmt_tree`].
+pub(crate) fn fmt_trees<N: Tree>(
+    trees: &[N],
+    f: &mut fmt::Formatter<'_>,
+    open: impl Fn(&N, &mut fmt::Formatter<'_>) -> fmt::Result,
+    close: &'static str,
+) -> fmt::Result {
+    f.write_str("[")?;
+    let mut todo = vec![Step::Text("]")];
+    push_children(&mut todo, trees);
+    walk(todo, f, open, close)
+}
+
+fn walk<N: Tree>(
+    mut todo: Vec<Step<'_, N>>,
+    f: &mut fmt::Formatter<'_>,
+    open: impl Fn(&N, &mut fmt::Formatter<'_>) -> fmt::Result,
+    close: &'static str,
+) -> fmt::Result {
+    while let Some(step) = todo.pop() {
+        match step {
+            Step::Text(text) => f.write_str(text)?,
+            Step::Open(node) => {
+                open(node, f)?;
+                f.write_str("[")?;
+                todo.push(Step::Text(close));
+                todo.push(Step::Text("]"));
+                push_children(&mut todo, node.children());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Pushes the children in reverse, so that they come off the stack in order.
+fn push_children<'a, N>(todo: &mut Vec<Step<'a, N>>, children: &'a [N]) {
+    for (i, child) in children.iter().enumerate().rev() {
+        todo.push(Step::Open(child));
+        if i > 0 {
+            todo.push(Step::Text(", "));
+        }
+    }
+}
@@ -1,4 +1,10 @@
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+use std::cmp::Ordering;
+use std::fmt::{self, Debug};
+use std::mem;
+
+use crate::tree::{clone_tree, cmp_trees, eq_trees, fmt_tree, fmt_trees, Tree};
+
+// Clone, PartialEq, PartialOrd and Debug are written by hand, to avoid recursion, see `tree.rs`.
 pub struct Witnessed<T> {
     pub item: T,
     pub count: usize,
@@ -19,15 +25,19 @@ impl<T> Witnessed<T> {
         self.children.add_child(child);
     }
 
+    /// Pre-order traversal, with an explicit stack instead of recursion.
     pub fn in_order(self, result: &mut Vec<T>) {
-        result.push(self.item);
-        for child in self.children.items {
-            child.in_order(result);
+        let mut todo = vec![self];
+        while let Some(Witnessed {
+            item, mut children, ..
+        }) = todo.pop()
+        {
+            result.push(item);
+            todo.extend(mem::take(&mut children.items).into_iter().rev());
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct WitnessedSet<T> {
     pub count: usize,
     pub items: Vec<Witnessed<T>>,
@@ -48,17 +58,147 @@ impl<T> WitnessedSet<T> {
         self.items.push(child);
     }
 
-    pub fn extend(&mut self, other: WitnessedSet<T>) {
+    pub fn extend(&mut self, mut other: WitnessedSet<T>) {
         self.count += other.count;
-        self.items.extend(other.items);
+        self.items.append(&mut other.items);
     }
-    pub fn in_order(self, result: &mut Vec<T>) {
-        for witnessed in self.items {
+    pub fn in_order(mut self, result: &mut Vec<T>) {
+        for witnessed in mem::take(&mut self.items) {
             witnessed.in_order(result);
         }
     }
 }
 
+// Like for `pairing::Children`: the derived drop would recurse once per level.
+impl<T> Drop for WitnessedSet<T> {
+    fn drop(&mut self) {
+        let mut todo = mem::take(&mut self.items);
+        while let Some(mut witnessed) = todo.pop() {
+            todo.append(&mut witnessed.children.items);
+        }
+    }
+}
+
+impl<T> Tree for Witnessed<T> {
+    fn children(&self) -> &[Self] {
+        &self.children.items
+    }
+    fn children_mut(&mut self) -> &mut Vec<Self> {
+        &mut self.children.items
+    }
+}
+
+impl<T: Clone> Clone for Witnessed<T> {
+    fn clone(&self) -> Self {
+        clone_tree(self, |node| Self {
+            item: node.item.clone(),
+            count: node.count,
+            children: WitnessedSet {
+                count: node.children.count,
+                items: Vec::with_capacity(node.children.items.len()),
+            },
+        })
+    }
+}
+
+impl<T: Clone> Clone for WitnessedSet<T> {
+    fn clone(&self) -> Self {
+        Self {
+            count: self.count,
+            items: self.items.iter().map(Witnessed::clone).collect(),
+        }
+    }
+}
+
+/// Compares everything but the children.
+fn eq_node<T: PartialEq>(a: &Witnessed<T>, b: &Witnessed<T>) -> bool {
+    a.item == b.item && a.count == b.count && a.children.count == b.children.count
+}
+
+impl<T: PartialEq> PartialEq for Witnessed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        eq_trees(
+            std::slice::from_ref(self),
+            std::slice::from_ref(other),
+            eq_node,
+        )
+    }
+}
+
+impl<T: Eq> Eq for Witnessed<T> {}
+
+impl<T: PartialEq> PartialEq for WitnessedSet<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.count == other.count && eq_trees(&self.items, &other.items, eq_node)
+    }
+}
+
+impl<T: Eq> Eq for WitnessedSet<T> {}
+
+/// Orders by everything but the children.
+fn cmp_node<T: PartialOrd>(a: &Witnessed<T>, b: &Witnessed<T>) -> Option<Ordering> {
+    match a.item.partial_cmp(&b.item)? {
+        Ordering::Equal => (a.count, a.children.count).partial_cmp(&(b.count, b.children.count)),
+        unequal => Some(unequal),
+    }
+}
+
+impl<T: PartialOrd> PartialOrd for Witnessed<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        cmp_trees(
+            std::slice::from_ref(self),
+            std::slice::from_ref(other),
+            cmp_node,
+        )
+    }
+}
+
+impl<T: Ord> Ord for Witnessed<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.partial_cmp(other)
+            .expect("total orders compare everything")
+    }
+}
+
+impl<T: PartialOrd> PartialOrd for WitnessedSet<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match self.count.cmp(&other.count) {
+            Ordering::Equal => cmp_trees(&self.items, &other.items, cmp_node),
+            unequal => Some(unequal),
+        }
+    }
+}
+
+impl<T: Ord> Ord for WitnessedSet<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.partial_cmp(other)
+            .expect("total orders compare everything")
+    }
+}
+
+/// Everything before the list of children.
+fn fmt_open<T: Debug>(node: &Witnessed<T>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+        f,
+        "Witnessed {{ item: {:?}, count: {}, children: WitnessedSet {{ count: {}, items: ",
+        node.item, node.count, node.children.count
+    )
+}
+
+impl<T: Debug> Debug for Witnessed<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_tree(self, f, fmt_open, " } }")
+    }
+}
+
+impl<T: Debug> Debug for WitnessedSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "WitnessedSet {{ count: {}, items: ", self.count)?;
+        fmt_trees(&self.items, f, fmt_open, " } }")?;
+        f.write_str(" }")
+    }
+}
+
 impl<T> From<WitnessedSet<T>> for Vec<T> {
     fn from(set: WitnessedSet<T>) -> Self {
         let mut result = Vec::new();